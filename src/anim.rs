@@ -0,0 +1,285 @@
+//! Frame-based sprite animation with support for Aseprite JSON sheets.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::Canvas;
+use crate::image::{Flip, Point, Rectangle, RGBA};
+use crate::json::{self, Value};
+
+/// A single image of a sprite sheet.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Frame {
+    /// Bounds of the frame inside the sheet image.
+    pub rect: Rectangle,
+    /// Position of the trimmed frame inside the untrimmed sprite.
+    pub offset: Point,
+    /// The frame is stored rotated 90° clockwise.
+    pub rotated: bool,
+    pub duration: Duration,
+}
+
+/// How a clip continues after its last frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// Starts over from the first frame.
+    Loop,
+    /// Plays backwards to the first frame, then forwards again.
+    PingPong,
+    /// Stops at the last frame.
+    Once,
+}
+
+/// A named sequence of frames.
+#[derive(Clone, Debug)]
+pub struct Clip {
+    pub name: String,
+    /// Indices into `Sheet::frames`.
+    pub frames: Vec<usize>,
+    pub mode: Mode,
+    /// Plays the frames from last to first.
+    pub reverse: bool,
+}
+
+impl Clip {
+    /// Returns the sheet frame shown at `time` since the clip started.
+    pub fn frame_at(&self, frames: &[Frame], time: Duration) -> Option<usize> {
+        let seq = self.sequence();
+        let total: Duration = seq.iter().map(|&i| frames[i].duration).sum();
+        if seq.is_empty() {
+            return None;
+        }
+        if total == Duration::from_secs(0) {
+            return seq.first().cloned();
+        }
+        if self.mode == Mode::Once && time >= total {
+            return seq.last().cloned();
+        }
+
+        let mut t = time.as_nanos() % total.as_nanos();
+        for &i in &seq {
+            let d = frames[i].duration.as_nanos();
+            if t < d {
+                return Some(i);
+            }
+            t -= d;
+        }
+        seq.last().cloned()
+    }
+
+    /// Returns whether a `Once` clip has reached its end at `time`.
+    pub fn is_finished(&self, frames: &[Frame], time: Duration) -> bool {
+        let total: Duration = self.sequence().iter().map(|&i| frames[i].duration).sum();
+        self.mode == Mode::Once && time >= total
+    }
+
+    /// Returns the frames of one full cycle.
+    fn sequence(&self) -> Vec<usize> {
+        let mut seq = self.frames.clone();
+        if self.reverse {
+            seq.reverse();
+        }
+        if self.mode == Mode::PingPong && seq.len() > 2 {
+            let back: Vec<usize> = seq[1..seq.len() - 1].iter().rev().cloned().collect();
+            seq.extend(back);
+        }
+        seq
+    }
+}
+
+/// Frames and clips sharing one image.
+#[derive(Clone, Debug, Default)]
+pub struct Sheet {
+    pub frames: Vec<Frame>,
+    pub clips: Vec<Clip>,
+    /// Image path as written in the sheet, for the caller to load.
+    pub image: String,
+}
+
+impl Sheet {
+    pub fn clip(&self, name: &str) -> Option<&Clip> {
+        self.clips.iter().find(|c| c.name == name)
+    }
+
+    /// Returns the pixels that `draw` covers for frame i at pos.
+    pub fn bounds(&self, i: usize, pos: Point) -> Rectangle {
+        let f = &self.frames[i];
+        let (w, h) = if f.rotated { (f.rect.dy(), f.rect.dx()) } else { (f.rect.dx(), f.rect.dy()) };
+        Rectangle::from_size(w, h) + Point { x: pos.x + f.offset.x, y: pos.y + f.offset.y }
+    }
+
+    /// Draws frame `i` from `image` with the top left corner of the untrimmed sprite at `pos`.
+    pub fn draw(&self, dst: &mut RGBA, image: &RGBA, i: usize, pos: Point) {
+        let f = &self.frames[i];
+        let dp = Point { x: pos.x + f.offset.x, y: pos.y + f.offset.y };
+        // A clockwise rotation is undone by a transposition and a vertical flip.
+        let flip = Flip { h: false, v: f.rotated, d: f.rotated };
+        dst.draw_over(dp, image, f.rect, flip, 0xFF);
+    }
+
+    /// Loads a sheet exported by Aseprite with either the hash or the array frame layout.
+    ///
+    /// Every frame tag becomes a clip; a sheet without tags gets a single looping
+    /// clip with all frames, named after the image.
+    /// Tags with a `repeat` count of 1 play once, other counts loop forever.
+    pub fn from_aseprite_json(src: &str) -> Result<Self, Error> {
+        let doc = json::parse(src)?;
+        let mut sheet = Sheet::default();
+
+        let frames: Vec<&Value> = match doc.get("frames") {
+            Value::Array(items) => items.iter().collect(),
+            Value::Object(members) => members.iter().map(|(_, v)| v).collect(),
+            _ => return Err(Error::Format("missing frames")),
+        };
+        for f in frames {
+            let r = f.get("frame");
+            let (x, y) = (num(r, "x")?, num(r, "y")?);
+            let rect = Rectangle {
+                min: Point { x, y },
+                max: Point { x: x + num(r, "w")?, y: y + num(r, "h")? },
+            };
+            let src = f.get("spriteSourceSize");
+            let offset = if src.is_null() {
+                Point { x: 0, y: 0 }
+            } else {
+                Point { x: num(src, "x")?, y: num(src, "y")? }
+            };
+            let ms = f.get("duration").as_f64().unwrap_or(100.0).max(0.0);
+            sheet.frames.push(Frame {
+                rect,
+                offset,
+                rotated: f.get("rotated").as_bool().unwrap_or(false),
+                duration: Duration::from_micros((ms * 1000.0) as u64),
+            });
+        }
+
+        let meta = doc.get("meta");
+        sheet.image = meta.get("image").as_str().unwrap_or("").to_string();
+
+        for tag in meta.get("frameTags").as_array().unwrap_or(&[]) {
+            let from = num(tag, "from")? as usize;
+            let to = num(tag, "to")? as usize;
+            if from > to || to >= sheet.frames.len() {
+                return Err(Error::Format("frame tag out of range"));
+            }
+            let (mode, reverse) = match tag.get("direction").as_str().unwrap_or("forward") {
+                "forward" => (Mode::Loop, false),
+                "reverse" => (Mode::Loop, true),
+                "pingpong" => (Mode::PingPong, false),
+                "pingpong_reverse" => (Mode::PingPong, true),
+                _ => return Err(Error::Format("unknown tag direction")),
+            };
+            let once = match tag.get("repeat") {
+                Value::String(n) => n.trim() == "1",
+                Value::Number(n) => *n == 1.0,
+                _ => false,
+            };
+            sheet.clips.push(Clip {
+                name: tag.get("name").as_str().unwrap_or("").to_string(),
+                frames: (from..=to).collect(),
+                mode: if once { Mode::Once } else { mode },
+                reverse,
+            });
+        }
+
+        if sheet.clips.is_empty() && !sheet.frames.is_empty() {
+            sheet.clips.push(Clip {
+                name: sheet.image.clone(),
+                frames: (0..sheet.frames.len()).collect(),
+                mode: Mode::Loop,
+                reverse: false,
+            });
+        }
+
+        Ok(sheet)
+    }
+}
+
+fn num(v: &Value, key: &str) -> Result<isize, Error> {
+    v.get(key).as_f64().map(|v| v as isize).ok_or(Error::Format("missing frame coordinate"))
+}
+
+/// Plays a clip in real time.
+pub struct Player {
+    pub clip: usize,
+    pub last_update: Instant,
+    /// Time since the clip started.
+    pub time: Duration,
+    /// Playback speed multiplier.
+    pub speed: f32,
+    pub paused: bool,
+}
+
+impl Player {
+    pub fn new(clip: usize) -> Self {
+        Self {
+            clip,
+            last_update: Instant::now(),
+            time: Duration::from_secs(0),
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    /// Switches to another clip from its first frame.
+    pub fn play(&mut self, clip: usize) {
+        self.clip = clip;
+        self.time = Duration::from_secs(0);
+        self.last_update = Instant::now();
+    }
+
+    /// Advances the playback by the time elapsed since the previous call.
+    pub fn update(&mut self) {
+        let elapsed = self.last_update.elapsed();
+        self.last_update += elapsed;
+        self.advance(elapsed);
+    }
+
+    /// Advances the playback by `dt`, scaled by `speed`.
+    pub fn advance(&mut self, dt: Duration) {
+        if self.paused {
+            return;
+        }
+        let nanos = dt.as_nanos() as f64 * self.speed.max(0.0) as f64;
+        self.time += Duration::from_nanos(nanos as u64);
+    }
+
+    /// Returns the current sheet frame.
+    pub fn frame(&self, sheet: &Sheet) -> Option<usize> {
+        sheet.clips.get(self.clip).and_then(|c| c.frame_at(&sheet.frames, self.time))
+    }
+
+    pub fn is_finished(&self, sheet: &Sheet) -> bool {
+        sheet.clips.get(self.clip).is_none_or(|c| c.is_finished(&sheet.frames, self.time))
+    }
+
+    /// Draws the current frame onto the canvas.
+    pub fn render(&self, canvas: &mut Canvas, sheet: &Sheet, image: &RGBA, x: isize, y: isize) {
+        if let Some(i) = self.frame(sheet) {
+            let pos = Point { x, y };
+            canvas.draw_rgba(sheet.bounds(i, pos), |dst| sheet.draw(dst, image, i, pos));
+        }
+    }
+}
+
+/// Error returned by `Sheet::from_aseprite_json`.
+#[derive(Debug)]
+pub enum Error {
+    Json(json::Error),
+    Format(&'static str),
+}
+
+impl From<json::Error> for Error {
+    fn from(err: json::Error) -> Self { Error::Json(err) }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Json(err) => write!(f, "invalid JSON: {}", err),
+            Error::Format(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
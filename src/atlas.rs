@@ -0,0 +1,670 @@
+//! Texture atlas: packs many small images into a few large pages.
+
+use std::collections::HashMap;
+
+use crate::image::{Point, Rectangle, RGBA};
+use crate::json::Value;
+
+/// Rectangle packing algorithm used for new pages.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Packing {
+    /// Bottom-left skyline. Fast, good for images of similar height.
+    Skyline,
+    /// Maximal rectangles with best short side fit. Slower, usually tighter.
+    MaxRects,
+}
+
+/// Location of a packed image.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Sprite {
+    /// Index of the page holding the image.
+    pub page: usize,
+    /// Bounds of the image on its page.
+    pub rect: Rectangle,
+    /// The image is stored rotated 90° clockwise,
+    /// so `rect` has the width and height of the source swapped.
+    pub rotated: bool,
+}
+
+struct Page {
+    pix: Vec<u8>,
+    packer: Packer,
+    // holes are the cells of replaced images, which are reused before the
+    // space of the packer.
+    holes: Vec<Rectangle>,
+}
+
+impl Page {
+    // pack returns the cell for a w x h rectangle and whether it is rotated.
+    fn pack(&mut self, w: isize, h: isize, rotate: bool) -> Option<(Rectangle, bool)> {
+        // Best short side fit among the holes, like MaxRects.
+        let mut best: Option<((isize, isize), usize, isize, isize)> = None;
+        for (i, f) in self.holes.iter().enumerate() {
+            let mut candidates = vec![(w, h)];
+            if rotate { candidates.push((h, w)) }
+            for (cw, ch) in candidates {
+                if cw > f.dx() || ch > f.dy() {
+                    continue;
+                }
+                let (lw, lh) = (f.dx() - cw, f.dy() - ch);
+                let score = (lw.min(lh), lw.max(lh));
+                if best.is_none_or(|(s, ..)| score < s) {
+                    best = Some((score, i, cw, ch));
+                }
+            }
+        }
+        if let Some((_, i, cw, ch)) = best {
+            // The rest of the hole is split in two, along the longer leftover.
+            let f = self.holes.swap_remove(i);
+            let cell = Rectangle::new(f.min.x, f.min.y, f.min.x + cw, f.min.y + ch);
+            let (right, below) = if f.dx() - cw > f.dy() - ch {
+                (Rectangle::new(cell.max.x, f.min.y, f.max.x, f.max.y),
+                 Rectangle::new(f.min.x, cell.max.y, cell.max.x, f.max.y))
+            } else {
+                (Rectangle::new(cell.max.x, f.min.y, f.max.x, cell.max.y),
+                 Rectangle::new(f.min.x, cell.max.y, f.max.x, f.max.y))
+            };
+            self.holes.extend([right, below].iter().filter(|r| !r.is_empty()));
+            return Some((cell, cw != w));
+        }
+
+        let (x, y, rotated) = self.packer.pack(w, h, rotate)?;
+        let (cw, ch) = if rotated { (h, w) } else { (w, h) };
+        Some((Rectangle::new(x, y, x + cw, y + ch), rotated))
+    }
+}
+
+/// Atlas builder and storage.
+///
+/// Every page has the same size. A new page is started when an image
+/// doesn't fit into any of the existing ones.
+pub struct Atlas {
+    size: (usize, usize),
+    pages: Vec<Page>,
+    // sprites holds the cell of each image on its page, with the padding.
+    sprites: Vec<(String, Sprite, Rectangle)>,
+    names: HashMap<String, usize>,
+
+    /// Empty pixels kept between images and around the page border.
+    pub padding: usize,
+    /// Allows storing images rotated by 90° when that packs better.
+    pub allow_rotation: bool,
+    /// Algorithm used for pages created from now on.
+    pub packing: Packing,
+    /// Maximal number of pages; zero means unlimited.
+    pub max_pages: usize,
+}
+
+impl Atlas {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            size: (width, height),
+            pages: Vec::new(),
+            sprites: Vec::new(),
+            names: HashMap::new(),
+            padding: 1,
+            allow_rotation: false,
+            packing: Packing::Skyline,
+            max_pages: 0,
+        }
+    }
+
+    /// Returns the size of each page.
+    pub fn size(&self) -> (usize, usize) { self.size }
+
+    /// Returns the number of pages.
+    pub fn pages(&self) -> usize { self.pages.len() }
+
+    /// Returns the pixels of page `i`, in R, G, B, A order.
+    pub fn page_pix(&self, i: usize) -> &[u8] { &self.pages[i].pix }
+
+    /// Returns page `i` as an image.
+    pub fn page_mut(&mut self, i: usize) -> RGBA<'_> {
+        let (w, h) = self.size;
+        let r = Rectangle::from_size(w as isize, h as isize);
+        RGBA::from_buf(&mut self.pages[i].pix, r)
+    }
+
+    /// Returns the location of the image inserted as `name`.
+    pub fn get(&self, name: &str) -> Option<Sprite> {
+        self.names.get(name).map(|&i| self.sprites[i].1)
+    }
+
+    /// Iterates over all packed images in insertion order.
+    pub fn sprites(&self) -> impl Iterator<Item=(&str, Sprite)> {
+        self.sprites.iter().map(|(name, s, _)| (name.as_str(), *s))
+    }
+
+    /// Packs `img` and copies its pixels into the atlas.
+    ///
+    /// Returns `None` if the image is larger than a page
+    /// or if all `max_pages` pages are full.
+    /// Inserting the same name again replaces the old image, whose pixels are
+    /// cleared and whose space is reused. If the new one doesn't fit, the old
+    /// one is kept.
+    pub fn insert(&mut self, name: &str, img: &RGBA) -> Option<Sprite> {
+        let (w, h) = (img.rect.dx(), img.rect.dy());
+        let pad = self.padding as isize;
+        let rotate = self.allow_rotation;
+
+        // The space of the old image is free for the new one.
+        let old = self.names.get(name).map(|&i| (i, self.sprites[i].1, self.sprites[i].2));
+        if let Some((_, s, cell)) = old {
+            self.pages[s.page].holes.push(cell);
+        }
+
+        let found = self.pages.iter_mut().enumerate()
+            .filter_map(|(i, p)| p.pack(w + pad, h + pad, rotate).map(|r| (i, r)))
+            .next();
+
+        let (page, (cell, rotated)) = match found {
+            Some(found) => found,
+            None => {
+                let full = self.max_pages != 0 && self.pages.len() >= self.max_pages;
+                let (pw, ph) = (self.size.0 as isize - pad, self.size.1 as isize - pad);
+                let mut page = Page {
+                    pix: vec![0; 4 * self.size.0 * self.size.1],
+                    packer: Packer::new(self.packing, pw, ph),
+                    holes: Vec::new(),
+                };
+                match page.pack(w + pad, h + pad, rotate) {
+                    Some(r) if !full => {
+                        self.pages.push(page);
+                        (self.pages.len() - 1, r)
+                    }
+                    _ => {
+                        // Nothing took the hole, so it is the last one.
+                        if let Some((_, s, _)) = old {
+                            self.pages[s.page].holes.pop();
+                        }
+                        return None;
+                    }
+                }
+            }
+        };
+
+        let min = Point { x: cell.min.x + pad, y: cell.min.y + pad };
+        let (dw, dh) = if rotated { (h, w) } else { (w, h) };
+        let rect = Rectangle { min, max: Point { x: min.x + dw, y: min.y + dh } };
+        let sprite = Sprite { page, rect, rotated };
+
+        if let Some((_, s, _)) = old {
+            let mut dst = self.page_mut(s.page);
+            for y in s.rect.min.y..s.rect.max.y {
+                for x in s.rect.min.x..s.rect.max.x {
+                    dst.set(x, y, [0; 4]);
+                }
+            }
+        }
+
+        let mut dst = self.page_mut(page);
+        for sy in 0..h {
+            for sx in 0..w {
+                let c = img.at(img.rect.min.x + sx, img.rect.min.y + sy);
+                let (dx, dy) = if rotated { (h - 1 - sy, sx) } else { (sx, sy) };
+                dst.set(min.x + dx, min.y + dy, c);
+            }
+        }
+
+        match old {
+            Some((i, ..)) => {
+                self.sprites[i].1 = sprite;
+                self.sprites[i].2 = cell;
+            }
+            None => {
+                self.names.insert(name.to_string(), self.sprites.len());
+                self.sprites.push((name.to_string(), sprite, cell));
+            }
+        }
+        Some(sprite)
+    }
+
+    /// Packs a batch of images, largest first, which usually wastes less space
+    /// than inserting them one by one in arbitrary order.
+    ///
+    /// Results are returned in the order of `images`.
+    pub fn insert_all(&mut self, images: &[(&str, &RGBA)]) -> Vec<Option<Sprite>> {
+        let mut order: Vec<usize> = (0..images.len()).collect();
+        order.sort_by_key(|&i| {
+            let r = images[i].1.rect;
+            std::cmp::Reverse((r.dy().max(r.dx()), r.dx().min(r.dy())))
+        });
+
+        let mut out = vec![None; images.len()];
+        for i in order {
+            let (name, img) = images[i];
+            out[i] = self.insert(name, img);
+        }
+        out
+    }
+
+    /// Describes the layout as JSON:
+    ///
+    /// `{"pages":[{"width":W,"height":H}],
+    ///   "sprites":[{"name":N,"page":P,"x":X,"y":Y,"w":W,"h":H,"rotated":R}]}`
+    pub fn manifest(&self) -> Value {
+        let (w, h) = self.size;
+        let pages = (0..self.pages.len())
+            .map(|_| Value::Object(vec![
+                ("width".into(), w.into()),
+                ("height".into(), h.into()),
+            ]))
+            .collect();
+        let sprites = self.sprites.iter()
+            .map(|(name, s, _)| Value::Object(vec![
+                ("name".into(), name.as_str().into()),
+                ("page".into(), s.page.into()),
+                ("x".into(), s.rect.min.x.into()),
+                ("y".into(), s.rect.min.y.into()),
+                ("w".into(), s.rect.dx().into()),
+                ("h".into(), s.rect.dy().into()),
+                ("rotated".into(), s.rotated.into()),
+            ]))
+            .collect();
+        Value::Object(vec![
+            ("pages".into(), Value::Array(pages)),
+            ("sprites".into(), Value::Array(sprites)),
+        ])
+    }
+}
+
+enum Packer {
+    Skyline(Skyline),
+    MaxRects(MaxRects),
+}
+
+impl Packer {
+    fn new(kind: Packing, w: isize, h: isize) -> Self {
+        match kind {
+            Packing::Skyline => Packer::Skyline(Skyline::new(w, h)),
+            Packing::MaxRects => Packer::MaxRects(MaxRects::new(w, h)),
+        }
+    }
+
+    /// Returns the position and the rotation of a `w` x `h` rectangle.
+    fn pack(&mut self, w: isize, h: isize, rotate: bool) -> Option<(isize, isize, bool)> {
+        match self {
+            Packer::Skyline(p) => p.pack(w, h, rotate),
+            Packer::MaxRects(p) => p.pack(w, h, rotate),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Node { x: isize, y: isize, w: isize }
+
+struct Skyline {
+    w: isize,
+    h: isize,
+    nodes: Vec<Node>,
+}
+
+impl Skyline {
+    fn new(w: isize, h: isize) -> Self {
+        Self { w, h, nodes: vec![Node { x: 0, y: 0, w }] }
+    }
+
+    /// Returns the lowest y where a `w` x `h` rectangle fits at node `i`.
+    fn fit(&self, i: usize, w: isize, h: isize) -> Option<isize> {
+        let x = self.nodes[i].x;
+        if w <= 0 || h <= 0 || x + w > self.w {
+            return None;
+        }
+        let mut left = w;
+        let mut y = 0;
+        for n in &self.nodes[i..] {
+            y = y.max(n.y);
+            if y + h > self.h {
+                return None;
+            }
+            left -= n.w;
+            if left <= 0 {
+                return Some(y);
+            }
+        }
+        None
+    }
+
+    /// Returns (node, x, y, top, node width) of the best bottom-left position.
+    fn find(&self, w: isize, h: isize) -> Option<(usize, isize, isize, isize, isize)> {
+        let mut best: Option<(usize, isize, isize, isize, isize)> = None;
+        for i in 0..self.nodes.len() {
+            if let Some(y) = self.fit(i, w, h) {
+                let n = self.nodes[i];
+                let better = match best {
+                    None => true,
+                    Some((_, _, _, top, nw)) => y + h < top || (y + h == top && n.w < nw),
+                };
+                if better {
+                    best = Some((i, n.x, y, y + h, n.w));
+                }
+            }
+        }
+        best
+    }
+
+    fn pack(&mut self, w: isize, h: isize, rotate: bool) -> Option<(isize, isize, bool)> {
+        let straight = self.find(w, h).map(|b| (b, false));
+        let rotated = if rotate { self.find(h, w).map(|b| (b, true)) } else { None };
+        let ((i, x, y, _, _), rotated) = match (straight, rotated) {
+            (Some(a), Some(b)) => if (b.0).3 < (a.0).3 { b } else { a },
+            (a, b) => a.or(b)?,
+        };
+        let (w, h) = if rotated { (h, w) } else { (w, h) };
+
+        self.nodes.insert(i, Node { x, y: y + h, w });
+
+        // Shrink or drop the nodes now covered by the new one.
+        let right = x + w;
+        while i + 1 < self.nodes.len() {
+            let n = &mut self.nodes[i + 1];
+            if n.x >= right {
+                break;
+            }
+            let shrink = right - n.x;
+            if n.w <= shrink {
+                self.nodes.remove(i + 1);
+            } else {
+                n.x += shrink;
+                n.w -= shrink;
+                break;
+            }
+        }
+
+        // Merge neighbours at the same height.
+        let mut j = 0;
+        while j + 1 < self.nodes.len() {
+            if self.nodes[j].y == self.nodes[j + 1].y {
+                self.nodes[j].w += self.nodes[j + 1].w;
+                self.nodes.remove(j + 1);
+            } else {
+                j += 1;
+            }
+        }
+
+        Some((x, y, rotated))
+    }
+}
+
+struct MaxRects {
+    free: Vec<Rectangle>,
+}
+
+impl MaxRects {
+    fn new(w: isize, h: isize) -> Self {
+        Self { free: vec![Rectangle::from_size(w, h)] }
+    }
+
+    fn pack(&mut self, w: isize, h: isize, rotate: bool) -> Option<(isize, isize, bool)> {
+        if w <= 0 || h <= 0 {
+            return None;
+        }
+
+        // Best short side fit, ties broken by the long side.
+        let mut best: Option<((isize, isize), Rectangle)> = None;
+        for f in &self.free {
+            let mut candidates = vec![(w, h)];
+            if rotate { candidates.push((h, w)) }
+            for (cw, ch) in candidates {
+                if cw > f.dx() || ch > f.dy() {
+                    continue;
+                }
+                let (lw, lh) = (f.dx() - cw, f.dy() - ch);
+                let score = (lw.min(lh), lw.max(lh));
+                if best.is_none_or(|(s, _)| score < s) {
+                    let min = f.min;
+                    best = Some((score, Rectangle { min, max: Point { x: min.x + cw, y: min.y + ch } }));
+                }
+            }
+        }
+        let (_, used) = best?;
+
+        let mut split = Vec::new();
+        self.free.retain(|f| {
+            let overlaps = used.min.x < f.max.x && f.min.x < used.max.x &&
+                           used.min.y < f.max.y && f.min.y < used.max.y;
+            if !overlaps {
+                return true;
+            }
+            if used.min.x > f.min.x {
+                split.push(Rectangle { min: f.min, max: Point { x: used.min.x, y: f.max.y } });
+            }
+            if used.max.x < f.max.x {
+                split.push(Rectangle { min: Point { x: used.max.x, y: f.min.y }, max: f.max });
+            }
+            if used.min.y > f.min.y {
+                split.push(Rectangle { min: f.min, max: Point { x: f.max.x, y: used.min.y } });
+            }
+            if used.max.y < f.max.y {
+                split.push(Rectangle { min: Point { x: f.min.x, y: used.max.y }, max: f.max });
+            }
+            false
+        });
+        self.free.extend(split);
+
+        // Drop free rectangles contained in other ones.
+        let contains = |a: &Rectangle, b: &Rectangle| {
+            a.min.x <= b.min.x && a.min.y <= b.min.y && b.max.x <= a.max.x && b.max.y <= a.max.y
+        };
+        let mut i = 0;
+        while i < self.free.len() {
+            let a = self.free[i];
+            let redundant = self.free.iter().enumerate()
+                .any(|(j, b)| j != i && contains(b, &a) && (a != *b || j < i));
+            if redundant {
+                self.free.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        Some((used.min.x, used.min.y, used.dx() != w))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // rand is xorshift32, to make images without a dependency.
+    fn rand(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    // image returns w x h pixels where every pixel is different,
+    // so a misplaced or misrotated copy is detected.
+    fn image(w: isize, h: isize, seed: u8) -> Vec<u8> {
+        let mut pix = Vec::new();
+        for y in 0..h {
+            for x in 0..w {
+                pix.extend_from_slice(&[x as u8, y as u8, seed, 255]);
+            }
+        }
+        pix
+    }
+
+    // check verifies that the sprites are inside their pages with a border of
+    // padding, are apart by padding, and hold the pixels of their images.
+    fn check(a: &Atlas, images: &HashMap<String, Vec<u8>>, sizes: &HashMap<String, (isize, isize)>) {
+        let pad = a.padding as isize;
+        let (pw, ph) = a.size();
+        let page = Rectangle::new(pad, pad, pw as isize - pad, ph as isize - pad);
+        let sprites: Vec<_> = a.sprites().collect();
+        for (i, &(name, s)) in sprites.iter().enumerate() {
+            assert!(s.page < a.pages());
+            assert_eq!(s.rect.intersect(page), s.rect, "{} outside of the page", name);
+            let (w, h) = sizes[name];
+            let (dw, dh) = if s.rotated { (h, w) } else { (w, h) };
+            assert_eq!((s.rect.dx(), s.rect.dy()), (dw, dh), "{}", name);
+
+            for &(other, t) in &sprites[i + 1..] {
+                if t.page != s.page { continue }
+                let grown = Rectangle::new(s.rect.min.x - pad, s.rect.min.y - pad, s.rect.max.x + pad, s.rect.max.y + pad);
+                assert!(!grown.overlaps(&t.rect), "{} and {} closer than the padding", name, other);
+            }
+
+            let pix = a.page_pix(s.page);
+            let src = &images[name];
+            for sy in 0..h {
+                for sx in 0..w {
+                    let (dx, dy) = if s.rotated { (h - 1 - sy, sx) } else { (sx, sy) };
+                    let (x, y) = (s.rect.min.x + dx, s.rect.min.y + dy);
+                    let o = 4 * (y as usize * pw + x as usize);
+                    let k = 4 * (sy * w + sx) as usize;
+                    assert_eq!(pix[o..o + 4], src[k..k + 4], "{} at {},{}", name, sx, sy);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packing() {
+        for packing in [Packing::Skyline, Packing::MaxRects] {
+            for (padding, rotation) in [(0, false), (1, false), (2, true)] {
+                let mut a = Atlas::new(64, 48);
+                a.packing = packing;
+                a.padding = padding;
+                a.allow_rotation = rotation;
+                let (mut images, mut sizes) = (HashMap::new(), HashMap::new());
+                let mut state = 1u32;
+                for i in 0..60 {
+                    let (w, h) = (1 + (rand(&mut state) % 20) as isize, 1 + (rand(&mut state) % 12) as isize);
+                    let name = format!("img{}", i);
+                    let mut pix = image(w, h, i as u8);
+                    let s = a.insert(&name, &RGBA::from_buf(&mut pix, Rectangle::from_size(w, h)));
+                    assert!(s.is_some(), "{:?} {}", packing, name);
+                    images.insert(name.clone(), pix);
+                    sizes.insert(name, (w, h));
+                }
+                assert!(a.pages() > 1);
+                check(&a, &images, &sizes);
+            }
+        }
+    }
+
+    #[test]
+    fn rotation() {
+        for packing in [Packing::Skyline, Packing::MaxRects] {
+            // The image only fits the page on its side.
+            let mut a = Atlas::new(32, 8);
+            a.packing = packing;
+            let mut pix = image(5, 20, 7);
+            let img = RGBA::from_buf(&mut pix, Rectangle::from_size(5, 20));
+            assert_eq!(a.insert("tall", &img), None);
+
+            a.allow_rotation = true;
+            let s = a.insert("tall", &img).unwrap();
+            assert!(s.rotated);
+            assert_eq!((s.rect.dx(), s.rect.dy()), (20, 5));
+            let images = HashMap::from([("tall".to_string(), pix.clone())]);
+            let sizes = HashMap::from([("tall".to_string(), (5, 20))]);
+            check(&a, &images, &sizes);
+        }
+    }
+
+    #[test]
+    fn replace() {
+        for packing in [Packing::Skyline, Packing::MaxRects] {
+            let mut a = Atlas::new(32, 32);
+            a.packing = packing;
+            a.max_pages = 1;
+            let (mut images, mut sizes) = (HashMap::new(), HashMap::new());
+            let mut state = 7u32;
+            for i in 0..500 {
+                // Sizes vary, but an image can always take the place of its
+                // previous version.
+                let name = format!("img{}", i % 4);
+                let (w, h) = match sizes.get(&name) {
+                    Some(&(w, h)) => (w - (rand(&mut state) % 2) as isize, h),
+                    None => (10, 10),
+                };
+                let (w, h) = if w < 4 { (10, 10) } else { (w, h) };
+                let mut pix = image(w, h, i as u8);
+                let s = a.insert(&name, &RGBA::from_buf(&mut pix, Rectangle::from_size(w, h)));
+                if s.is_none() {
+                    // The page is full for a larger image; the old one stays.
+                    assert!(w > sizes[&name].0, "{:?} {} doesn't fit its own place", packing, name);
+                    let old = a.get(&name).unwrap();
+                    assert_eq!((old.rect.dx(), old.rect.dy()), sizes[&name]);
+                    continue;
+                }
+                images.insert(name.clone(), pix);
+                sizes.insert(name, (w, h));
+            }
+            assert_eq!(a.pages(), 1);
+            assert_eq!(a.sprites().count(), 4);
+            check(&a, &images, &sizes);
+
+            // Replaced images leave no pixels behind.
+            let mut used = vec![false; 32 * 32];
+            for (_, s) in a.sprites() {
+                for y in s.rect.min.y..s.rect.max.y {
+                    for x in s.rect.min.x..s.rect.max.x {
+                        used[y as usize * 32 + x as usize] = true;
+                    }
+                }
+            }
+            let pix = a.page_pix(0);
+            for (i, &u) in used.iter().enumerate() {
+                assert!(u || pix[4 * i..4 * i + 4] == [0; 4], "pixel {} not cleared", i);
+            }
+        }
+    }
+
+    #[test]
+    fn replace_larger() {
+        // A larger image doesn't fit the hole of the old one, which is then
+        // used by a smaller image.
+        let mut a = Atlas::new(16, 16);
+        a.padding = 0;
+        a.max_pages = 1;
+        let mut pix = image(16, 8, 0);
+        let first = a.insert("a", &RGBA::from_buf(&mut pix, Rectangle::from_size(16, 8))).unwrap();
+        a.insert("b", &RGBA::from_buf(&mut pix, Rectangle::from_size(16, 8))).unwrap();
+        let mut big = image(16, 16, 1);
+        assert_eq!(a.insert("a", &RGBA::from_buf(&mut big, Rectangle::from_size(16, 16))), None);
+        assert_eq!(a.get("a"), Some(first));
+        assert_eq!(a.page_pix(0)[..4], [0, 0, 0, 255]);
+
+        let mut small = image(8, 8, 2);
+        let s = a.insert("a", &RGBA::from_buf(&mut small, Rectangle::from_size(8, 8))).unwrap();
+        assert_eq!(s.rect, Rectangle::new(0, 0, 8, 8));
+        let s = a.insert("c", &RGBA::from_buf(&mut small, Rectangle::from_size(8, 8))).unwrap();
+        assert_eq!(s.rect, Rectangle::new(8, 0, 16, 8));
+    }
+
+    #[test]
+    fn manifest() {
+        let mut a = Atlas::new(16, 16);
+        a.allow_rotation = true;
+        let mut pix = image(14, 3, 0);
+        a.insert("wide", &RGBA::from_buf(&mut pix, Rectangle::from_size(14, 3))).unwrap();
+        let mut pix = image(3, 14, 0);
+        a.insert("tall", &RGBA::from_buf(&mut pix, Rectangle::from_size(3, 14))).unwrap();
+        let mut pix = image(14, 14, 0);
+        a.insert("big", &RGBA::from_buf(&mut pix, Rectangle::from_size(14, 14))).unwrap();
+
+        let m = crate::json::parse(&a.manifest().to_string()).unwrap();
+        let pages = m.get("pages").as_array().unwrap();
+        assert_eq!(pages.len(), a.pages());
+        for p in pages {
+            assert_eq!((p.get("width").as_i64(), p.get("height").as_i64()), (Some(16), Some(16)));
+        }
+        let sprites = m.get("sprites").as_array().unwrap();
+        assert_eq!(sprites.len(), 3);
+        for (v, (name, s)) in sprites.iter().zip(a.sprites()) {
+            assert_eq!(v.get("name").as_str(), Some(name));
+            assert_eq!(v.get("page").as_i64(), Some(s.page as i64));
+            assert_eq!(v.get("x").as_i64(), Some(s.rect.min.x as i64));
+            assert_eq!(v.get("y").as_i64(), Some(s.rect.min.y as i64));
+            assert_eq!(v.get("w").as_i64(), Some(s.rect.dx() as i64));
+            assert_eq!(v.get("h").as_i64(), Some(s.rect.dy() as i64));
+            assert_eq!(v.get("rotated").as_bool(), Some(s.rotated));
+        }
+        assert_eq!(sprites[1].get("rotated").as_bool(), Some(true));
+        assert_eq!(sprites[2].get("page").as_i64(), Some(1));
+    }
+}
@@ -1,5 +1,12 @@
 // Package fixed implements fixed-point integer types.
 
+use crate::image::{PointF, Rectangle, RectangleF};
+
+mod index;
+mod region;
+pub use self::index::{Grid, QuadTree, SpatialIndex};
+pub use self::region::{Band, Region};
+
 pub trait AnyNum:
     Copy + Clone + Default + PartialEq + PartialOrd +
     std::ops::Add<Output=Self> +
@@ -9,6 +16,9 @@ pub trait AnyNum:
     fn round(&self) -> isize;
     fn ceil(&self) -> isize;
     fn mul(self, other: Self) -> Self;
+    fn to_f64(self) -> f64;
+    /// Returns the nearest value to v.
+    fn from_f64(v: f64) -> Self;
 }
 
 pub trait AnyPoint: Copy + Clone + Default + PartialEq +
@@ -17,6 +27,7 @@ pub trait AnyPoint: Copy + Clone + Default + PartialEq +
 {
     type Num: AnyNum;
 
+    fn new(x: Self::Num, y: Self::Num) -> Self;
     fn x(&self) -> Self::Num;
     fn y(&self) -> Self::Num;
 
@@ -28,6 +39,7 @@ pub trait AnyRect: Copy + Clone + Default + PartialEq {
     type Num: AnyNum;
     type Point: AnyPoint<Num=Self::Num>;
 
+    fn from_points(min: Self::Point, max: Self::Point) -> Self;
     fn min(&self) -> Self::Point;
     fn max(&self) -> Self::Point;
 
@@ -39,24 +51,18 @@ pub trait AnyRect: Copy + Clone + Default + PartialEq {
 
     #[inline(always)]
     /// Returns whether the rectangle contains no points.
-    fn is_empty(&self) -> bool
-        where Self::Num: Ord
-    {
+    fn is_empty(&self) -> bool {
         self.min().x() >= self.max().x() || self.min().y() >= self.max().y()
     }
 
     #[inline(always)]
-    fn contains_point(&self, p: &Self::Point) -> bool
-        where Self::Num: Ord
-    {
+    fn contains_point(&self, p: &Self::Point) -> bool {
         self.min().x() <= p.x() && p.x() < self.max().x() &&
         self.min().y() <= p.y() && p.y() < self.max().y()
     }
 
     #[inline(always)]
-    fn contains_rect(&self, other: &Self) -> bool
-        where Self::Num: Ord
-    {
+    fn contains_rect(&self, other: &Self) -> bool {
         if self.is_empty() {
             true
         } else {
@@ -72,20 +78,64 @@ pub trait AnyRect: Copy + Clone + Default + PartialEq {
     fn union(self, other: Self) -> Self;
 }
 
-#[doc(hidden)]
-macro impl_fixed($name:ident, $point:ident, $rect:ident, $inner:ident, $outer:ty, $shift:expr) {
+impl AnyNum for isize {
+    #[inline(always)] fn floor(&self) -> isize { *self }
+    #[inline(always)] fn round(&self) -> isize { *self }
+    #[inline(always)] fn ceil(&self) -> isize { *self }
+    #[inline(always)] fn mul(self, other: Self) -> Self { self * other }
+    #[inline(always)] fn to_f64(self) -> f64 { self as f64 }
+    #[inline(always)] fn from_f64(v: f64) -> Self { v.round() as isize }
+}
+
+impl AnyNum for i32 {
+    #[inline(always)] fn floor(&self) -> isize { *self as isize }
+    #[inline(always)] fn round(&self) -> isize { *self as isize }
+    #[inline(always)] fn ceil(&self) -> isize { *self as isize }
+    #[inline(always)] fn mul(self, other: Self) -> Self { self * other }
+    #[inline(always)] fn to_f64(self) -> f64 { self as f64 }
+    #[inline(always)] fn from_f64(v: f64) -> Self { v.round() as i32 }
+}
+
+impl AnyNum for f32 {
+    #[inline(always)] fn floor(&self) -> isize { f32::floor(*self) as isize }
+    // Ties are rounded up, like for the fixed-point types.
+    #[inline(always)] fn round(&self) -> isize { f32::floor(*self + 0.5) as isize }
+    #[inline(always)] fn ceil(&self) -> isize { f32::ceil(*self) as isize }
+    #[inline(always)] fn mul(self, other: Self) -> Self { self * other }
+    #[inline(always)] fn to_f64(self) -> f64 { self as f64 }
+    #[inline(always)] fn from_f64(v: f64) -> Self { v as f32 }
+}
+
+impl AnyNum for f64 {
+    #[inline(always)] fn floor(&self) -> isize { f64::floor(*self) as isize }
+    #[inline(always)] fn round(&self) -> isize { f64::floor(*self + 0.5) as isize }
+    #[inline(always)] fn ceil(&self) -> isize { f64::ceil(*self) as isize }
+    #[inline(always)] fn mul(self, other: Self) -> Self { self * other }
+    #[inline(always)] fn to_f64(self) -> f64 { self }
+    #[inline(always)] fn from_f64(v: f64) -> Self { v }
+}
+
+// impl_fixed is a macro_rules! macro because the methods and constants that a
+// `macro` defines are hygienic: they could not be used outside of it.
+macro_rules! impl_fixed {
+    ($name:ident, $point:ident, $rect:ident, $inner:ident, $outer:ty, $shift:expr, $digits:expr) => {
     /// Fixed-point number.
     #[derive(Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
     pub struct $name(pub $inner);
 
     impl $name {
+        pub const ZERO: Self = $name(0);
+        pub const ONE: Self = $name(1 << $shift);
+        pub const MIN: Self = $name($inner::MIN);
+        pub const MAX: Self = $name($inner::MAX);
+
         /// Returns the greatest integer value less than or equal to self.
         #[inline(always)]
         pub fn floor(&self) -> isize { (self.0 >> $shift) as isize }
         /// Returns the nearest integer value to self. Ties are rounded up.
         #[inline(always)]
         pub fn round(&self) -> isize {
-            ($inner::wrapping_add(self.0, (1 << $shift) - 1) >> $shift) as isize
+            ($inner::wrapping_add(self.0, 1 << ($shift - 1)) >> $shift) as isize
         }
         /// Returns the least integer value greater than or equal to self.
         #[inline(always)]
@@ -93,13 +143,65 @@ macro impl_fixed($name:ident, $point:ident, $rect:ident, $inner:ident, $outer:ty
             ($inner::wrapping_add(self.0, (1 << $shift)).wrapping_sub(1) >> $shift) as isize
         }
 
+        /// Returns x*y, rounded to the nearest value. Ties are rounded up.
+        /// The product wraps around if it does not fit.
         #[inline(always)]
         pub fn mul(self, other: Self) -> Self {
-            let x = self.0 as $outer;
-            let y = other.0 as $outer;
-            let value = (x * y + 1<<($shift-1)) >> $shift;
-            $name(value as $inner)
+            $name(Self::wide_mul(self, other) as $inner)
+        }
+
+        #[inline(always)]
+        fn wide_mul(x: Self, y: Self) -> $outer {
+            (x.0 as $outer * y.0 as $outer + (1 << ($shift - 1))) >> $shift
+        }
+
+        #[inline(always)]
+        fn wide_div(x: Self, y: Self) -> $outer {
+            let (n, d) = ((x.0 as $outer) << $shift, y.0 as $outer);
+            let (q, r) = (n / d, n % d);
+            if 2 * r.abs() >= d.abs() {
+                q + (n.signum() * d.signum())
+            } else {
+                q
+            }
+        }
+
+        #[inline(always)]
+        fn narrow(v: $outer) -> Option<Self> {
+            if v < $inner::MIN as $outer || v > $inner::MAX as $outer { None } else { Some($name(v as $inner)) }
         }
+
+        #[inline(always)]
+        fn saturate(v: $outer) -> Self {
+            Self::narrow(v).unwrap_or(if v < 0 { Self::MIN } else { Self::MAX })
+        }
+
+        pub fn checked_add(self, other: Self) -> Option<Self> { self.0.checked_add(other.0).map($name) }
+        pub fn checked_sub(self, other: Self) -> Option<Self> { self.0.checked_sub(other.0).map($name) }
+        pub fn checked_mul(self, other: Self) -> Option<Self> { Self::narrow(Self::wide_mul(self, other)) }
+        /// Returns `None` if y is zero or the quotient does not fit.
+        pub fn checked_div(self, other: Self) -> Option<Self> {
+            if other.0 == 0 { None } else { Self::narrow(Self::wide_div(self, other)) }
+        }
+        pub fn checked_neg(self) -> Option<Self> { self.0.checked_neg().map($name) }
+
+        pub fn saturating_add(self, other: Self) -> Self { $name(self.0.saturating_add(other.0)) }
+        pub fn saturating_sub(self, other: Self) -> Self { $name(self.0.saturating_sub(other.0)) }
+        pub fn saturating_mul(self, other: Self) -> Self { Self::saturate(Self::wide_mul(self, other)) }
+        /// Panics if y is zero.
+        pub fn saturating_div(self, other: Self) -> Self { Self::saturate(Self::wide_div(self, other)) }
+
+        pub fn abs(self) -> Self { $name(self.0.abs()) }
+
+        /// Returns the nearest fixed-point value to v. Values out of
+        /// range saturate and NaN gives zero.
+        pub fn from_f32(v: f32) -> Self { $name((v as f64 * (1 << $shift) as f64).round() as $inner) }
+        /// Returns the nearest fixed-point value to v. Values out of
+        /// range saturate and NaN gives zero.
+        pub fn from_f64(v: f64) -> Self { $name((v * (1 << $shift) as f64).round() as $inner) }
+
+        pub fn to_f32(self) -> f32 { self.to_f64() as f32 }
+        pub fn to_f64(self) -> f64 { self.0 as f64 / (1 << $shift) as f64 }
     }
 
     impl From<$inner> for $name {
@@ -107,6 +209,38 @@ macro impl_fixed($name:ident, $point:ident, $rect:ident, $inner:ident, $outer:ty
         fn from(i: $inner) -> Self { $name(i << $shift) }
     }
 
+    impl From<f32> for $name {
+        fn from(v: f32) -> Self { Self::from_f32(v) }
+    }
+
+    impl From<f64> for $name {
+        fn from(v: f64) -> Self { Self::from_f64(v) }
+    }
+
+    impl From<$name> for f32 {
+        fn from(v: $name) -> Self { v.to_f32() }
+    }
+
+    impl From<$name> for f64 {
+        fn from(v: $name) -> Self { v.to_f64() }
+    }
+
+    /// Formats the value like Go's fixed package: the integer part and the
+    /// fractional part in units of 1/2^shift, so that 1.5 is "1:32" for I26_6.
+    impl std::fmt::Debug for $name {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            let v = self.0 as $outer;
+            let (sign, v) = if v < 0 { ("-", -v) } else { ("", v) };
+            write!(f, "{}{}:{:0w$}", sign, v >> $shift, v & ((1 << $shift) - 1), w = $digits)
+        }
+    }
+
+    impl std::fmt::Display for $name {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            std::fmt::Display::fmt(&self.to_f64(), f)
+        }
+    }
+
     impl std::ops::Add for $name {
         type Output = Self;
         #[inline(always)]
@@ -117,9 +251,51 @@ macro impl_fixed($name:ident, $point:ident, $rect:ident, $inner:ident, $outer:ty
         #[inline(always)]
         fn sub(self, other: Self) -> Self { $name(self.0 - other.0) }
     }
+    impl std::ops::Mul for $name {
+        type Output = Self;
+        #[inline(always)]
+        fn mul(self, other: Self) -> Self { $name::mul(self, other) }
+    }
+    /// Returns x/y, rounded to the nearest value. Ties are rounded away
+    /// from zero. Panics if y is zero.
+    impl std::ops::Div for $name {
+        type Output = Self;
+        #[inline(always)]
+        fn div(self, other: Self) -> Self { $name(Self::wide_div(self, other) as $inner) }
+    }
+    impl std::ops::Neg for $name {
+        type Output = Self;
+        #[inline(always)]
+        fn neg(self) -> Self { $name(-self.0) }
+    }
+    impl std::ops::AddAssign for $name {
+        #[inline(always)]
+        fn add_assign(&mut self, other: Self) { self.0 += other.0 }
+    }
+    impl std::ops::SubAssign for $name {
+        #[inline(always)]
+        fn sub_assign(&mut self, other: Self) { self.0 -= other.0 }
+    }
+    impl std::ops::MulAssign for $name {
+        #[inline(always)]
+        fn mul_assign(&mut self, other: Self) { *self = *self * other }
+    }
+    impl std::ops::DivAssign for $name {
+        #[inline(always)]
+        fn div_assign(&mut self, other: Self) { *self = *self / other }
+    }
+
+    impl AnyNum for $name {
+        #[inline(always)] fn floor(&self) -> isize { $name::floor(self) }
+        #[inline(always)] fn round(&self) -> isize { $name::round(self) }
+        #[inline(always)] fn ceil(&self) -> isize { $name::ceil(self) }
+        #[inline(always)] fn mul(self, other: Self) -> Self { $name::mul(self, other) }
+        #[inline(always)] fn to_f64(self) -> f64 { $name::to_f64(self) }
+        #[inline(always)] fn from_f64(v: f64) -> Self { $name::from_f64(v) }
+    }
 
     /// Fixed-point coordinate pair.
-    #[derive(Copy, Clone, Default, PartialEq, Eq)]
+    #[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
     pub struct $point {
         pub x: $name,
         pub y: $name,
@@ -161,8 +337,8 @@ macro impl_fixed($name:ident, $point:ident, $rect:ident, $inner:ident, $outer:ty
         #[inline(always)]
         fn mul(self, k: $name) -> Self {
             Self {
-                x: $name(self.x.0 * k.0 / (1 << $shift)),
-                y: $name(self.y.0 * k.0 / (1 << $shift)),
+                x: self.x * k,
+                y: self.y * k,
             }
         }
     }
@@ -172,16 +348,25 @@ macro impl_fixed($name:ident, $point:ident, $rect:ident, $inner:ident, $outer:ty
         #[inline(always)]
         fn div(self, k: $name) -> Self {
             Self {
-                x: $name(self.x.0 * (1 << $shift) / k.0),
-                y: $name(self.y.0 * (1 << $shift) / k.0),
+                x: self.x / k,
+                y: self.y / k,
             }
         }
     }
 
+    impl AnyPoint for $point {
+        type Num = $name;
+        #[inline(always)] fn new(x: $name, y: $name) -> Self { Self { x, y } }
+        #[inline(always)] fn x(&self) -> $name { self.x }
+        #[inline(always)] fn y(&self) -> $name { self.y }
+        #[inline(always)] fn mul(self, rhs: $name) -> Self { self * rhs }
+        #[inline(always)] fn div(self, rhs: $name) -> Self { self / rhs }
+    }
+
     /// Fixed-point coordinate rectangle.
     /// The Min bound is inclusive and the Max bound is exclusive.
     /// It is well-formed if Min.X <= Max.X and likewise for Y.
-    #[derive(Copy, Clone, Default, PartialEq, Eq)]
+    #[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
     pub struct $rect {
         pub min: $point,
         pub max: $point,
@@ -269,7 +454,376 @@ macro impl_fixed($name:ident, $point:ident, $rect:ident, $inner:ident, $outer:ty
             }
         }
     }
+
+    impl AnyRect for $rect {
+        type Num = $name;
+        type Point = $point;
+
+        #[inline(always)] fn from_points(min: $point, max: $point) -> Self { Self { min, max } }
+        #[inline(always)] fn min(&self) -> $point { self.min }
+        #[inline(always)] fn max(&self) -> $point { self.max }
+
+        #[inline(always)] fn add(self, rhs: $point) -> Self { self + rhs }
+        #[inline(always)] fn sub(self, rhs: $point) -> Self { self - rhs }
+
+        fn maybe_intersect(self, other: Self) -> Option<Self> { $rect::maybe_intersect(self, other) }
+        fn intersect(self, other: Self) -> Self { $rect::intersect(self, other) }
+        fn union(self, other: Self) -> Self { $rect::union(self, other) }
+    }
+}
+}
+
+impl_fixed!(I26_6 , P26_6 , R26_6 , i32, i64, 6, 2);
+impl_fixed!(I52_12, P52_12, R52_12, i64, i128, 12, 4);
+
+/// 2x3 affine transform, mapping (x, y) to `(a*x + c*y + e, b*x + d*y + f)`.
+///
+/// It has the layout of the `[a, b, c, d, e, f]` matrices of `vg`, and
+/// converts to and from them.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+/// The parts of a `Transform`, as returned by `Transform::decompose`.
+///
+/// The transform scales, then skews along x, then rotates, then translates.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Decomposed {
+    pub translation: [f32; 2],
+    /// In radians.
+    pub rotation: f32,
+    /// The angle of the skew along x, in radians.
+    pub skew: f32,
+    pub scale: [f32; 2],
+}
+
+impl Default for Transform {
+    fn default() -> Self { Self::IDENTITY }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self { a: 1.0, b: 0.0, c: 0.0, d: 1.0, e: 0.0, f: 0.0 };
+
+    pub const fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    pub const fn translate(tx: f32, ty: f32) -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, tx, ty)
+    }
+
+    pub const fn scale(sx: f32, sy: f32) -> Self {
+        Self::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    /// Returns the rotation by `angle` radians, clockwise on screen.
+    pub fn rotate(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Returns the rotation by `angle` radians around (cx, cy).
+    pub fn rotate_around(angle: f32, cx: f32, cy: f32) -> Self {
+        Self::translate(cx, cy) * Self::rotate(angle) * Self::translate(-cx, -cy)
+    }
+
+    /// Returns the skew by the angles `ax` along x and `ay` along y, in
+    /// radians, like the SVG `skewX` and `skewY` transforms.
+    pub fn skew(ax: f32, ay: f32) -> Self {
+        Self::new(1.0, ay.tan(), ax.tan(), 1.0, 0.0, 0.0)
+    }
+
+    /// Returns the transform that applies self, then `next`.
+    pub fn then(self, next: Self) -> Self { next * self }
+
+    pub fn then_translate(self, tx: f32, ty: f32) -> Self { self.then(Self::translate(tx, ty)) }
+    pub fn then_scale(self, sx: f32, sy: f32) -> Self { self.then(Self::scale(sx, sy)) }
+    pub fn then_rotate(self, angle: f32) -> Self { self.then(Self::rotate(angle)) }
+    pub fn then_skew(self, ax: f32, ay: f32) -> Self { self.then(Self::skew(ax, ay)) }
+
+    pub fn determinant(&self) -> f32 { self.a * self.d - self.b * self.c }
+
+    pub fn is_identity(&self) -> bool { *self == Self::IDENTITY }
+
+    /// Returns the inverse transform, or `None` if the transform collapses
+    /// the plane onto a line or a point.
+    pub fn inverse(&self) -> Option<Self> {
+        let Self { a, b, c, d, e, f } = *self;
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None
+        }
+        let inv = 1.0 / det;
+        Some(Self::new(
+            d * inv, -b * inv,
+            -c * inv, a * inv,
+            (c * f - d * e) * inv, (b * e - a * f) * inv,
+        ))
+    }
+
+    /// Returns the point p transformed.
+    pub fn apply(&self, p: [f32; 2]) -> [f32; 2] {
+        [self.a * p[0] + self.c * p[1] + self.e, self.b * p[0] + self.d * p[1] + self.f]
+    }
+
+    /// Returns the vector v transformed, which ignores the translation.
+    pub fn apply_vector(&self, v: [f32; 2]) -> [f32; 2] {
+        [self.a * v[0] + self.c * v[1], self.b * v[0] + self.d * v[1]]
+    }
+
+    pub fn transform_point(&self, p: PointF) -> PointF {
+        let [x, y] = self.apply([p.x, p.y]);
+        PointF { x, y }
+    }
+
+    /// Returns the smallest rectangle that contains the transformed corners of r.
+    pub fn transform_rect(&self, r: RectangleF) -> RectangleF {
+        let corners = [
+            self.apply([r.min.x, r.min.y]),
+            self.apply([r.max.x, r.min.y]),
+            self.apply([r.min.x, r.max.y]),
+            self.apply([r.max.x, r.max.y]),
+        ];
+        let mut out = RectangleF {
+            min: PointF { x: corners[0][0], y: corners[0][1] },
+            max: PointF { x: corners[0][0], y: corners[0][1] },
+        };
+        for p in &corners[1..] {
+            out.min.x = out.min.x.min(p[0]);
+            out.min.y = out.min.y.min(p[1]);
+            out.max.x = out.max.x.max(p[0]);
+            out.max.y = out.max.y.max(p[1]);
+        }
+        out
+    }
+
+    /// Returns the smallest rectangle of pixels that contains r transformed.
+    pub fn transform_pixels(&self, r: Rectangle) -> Rectangle {
+        Rectangle::from_f32(self.transform_rect(r.into()))
+    }
+
+    /// Splits the transform into its parts, or returns `None` if it has no
+    /// inverse. Mirroring is kept in the y scale.
+    pub fn decompose(&self) -> Option<Decomposed> {
+        let Self { a, b, c, d, e, f } = *self;
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None
+        }
+        let sx = (a * a + b * b).sqrt();
+        Some(Decomposed {
+            translation: [e, f],
+            rotation: b.atan2(a),
+            skew: ((a * c + b * d) / det).atan(),
+            scale: [sx, det / sx],
+        })
+    }
+}
+
+impl From<Decomposed> for Transform {
+    fn from(p: Decomposed) -> Self {
+        Transform::scale(p.scale[0], p.scale[1])
+            .then_skew(p.skew, 0.0)
+            .then_rotate(p.rotation)
+            .then_translate(p.translation[0], p.translation[1])
+    }
+}
+
+impl std::ops::Mul for Transform {
+    type Output = Self;
+    /// Returns the transform that applies `rhs`, then self, as matrices multiply.
+    fn mul(self, n: Self) -> Self {
+        let m = self;
+        Self::new(
+            m.a * n.a + m.c * n.b,
+            m.b * n.a + m.d * n.b,
+            m.a * n.c + m.c * n.d,
+            m.b * n.c + m.d * n.d,
+            m.a * n.e + m.c * n.f + m.e,
+            m.b * n.e + m.d * n.f + m.f,
+        )
+    }
 }
 
-impl_fixed!(I26_6 , P26_6 , R26_6 , i32, i64, 6);
-impl_fixed!(I52_12, P52_12, R52_12, i64, i128, 12);
+impl std::ops::MulAssign for Transform {
+    fn mul_assign(&mut self, rhs: Self) { *self = *self * rhs }
+}
+
+impl From<[f32; 6]> for Transform {
+    fn from(m: [f32; 6]) -> Self { Self::new(m[0], m[1], m[2], m[3], m[4], m[5]) }
+}
+
+impl From<Transform> for [f32; 6] {
+    fn from(t: Transform) -> Self { [t.a, t.b, t.c, t.d, t.e, t.f] }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // rand is xorshift32, to pick operands without a dependency.
+    fn rand(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    // Operands of up to 21 bits, so that their products and scaled
+    // dividends are exact in f64, with the small values and the ties. Some
+    // products do not fit in I26_6.
+    fn operands() -> Vec<i32> {
+        let mut v: Vec<i32> = (-260..=260).collect();
+        let mut state = 1;
+        for _ in 0..200 {
+            v.push((rand(&mut state) as i32) >> 11);
+        }
+        v
+    }
+
+    macro_rules! fixed_tests {
+        ($mod:ident, $name:ident, $inner:ident, $shift:expr) => {
+            mod $mod {
+                use super::*;
+
+                type F = $name;
+                const ONE: f64 = (1 << $shift) as f64;
+
+                fn f(v: i32) -> F { $name(v as $inner) }
+
+                #[test]
+                fn round() {
+                    for v in operands() {
+                        let x = v as f64 / ONE;
+                        assert_eq!(f(v).floor(), x.floor() as isize, "{}", v);
+                        assert_eq!(f(v).ceil(), x.ceil() as isize, "{}", v);
+                        // Ties are rounded up.
+                        assert_eq!(f(v).round(), (x + 0.5).floor() as isize, "{}", v);
+                    }
+                    let half = 1 << ($shift - 1);
+                    assert_eq!(f(half).round(), 1);
+                    assert_eq!(f(-half).round(), 0);
+                    assert_eq!(f(3 * half).round(), 2);
+                    assert_eq!(f(-3 * half).round(), -1);
+                    assert_eq!(F::MIN.floor(), ($inner::MIN >> $shift) as isize);
+                    assert_eq!(F::MAX.floor(), ($inner::MAX >> $shift) as isize);
+                }
+
+                #[test]
+                fn mul() {
+                    let ops = operands();
+                    for &a in &ops {
+                        for &b in ops.iter().step_by(7) {
+                            // Ties are rounded up.
+                            let want = (a as f64 * b as f64 / ONE + 0.5).floor();
+                            if want < $inner::MIN as f64 || want > $inner::MAX as f64 {
+                                assert_eq!(f(a).checked_mul(f(b)), None, "{} * {}", a, b);
+                                let sat = if want < 0.0 { F::MIN } else { F::MAX };
+                                assert_eq!(f(a).saturating_mul(f(b)), sat, "{} * {}", a, b);
+                                continue
+                            }
+                            let got = f(a) * f(b);
+                            assert_eq!(got.0 as f64, want, "{} * {}", a, b);
+                            assert_eq!(f(a).checked_mul(f(b)), Some(got));
+                            assert_eq!(f(a).saturating_mul(f(b)), got);
+                            let mut x = f(a);
+                            x *= f(b);
+                            assert_eq!(x, got);
+                        }
+                    }
+                    assert_eq!(f(1) * f(1 << ($shift - 1)), f(1));
+                    assert_eq!(f(-1) * f(1 << ($shift - 1)), f(0));
+
+                    assert_eq!(F::MAX * F::ONE, F::MAX);
+                    assert_eq!(F::MIN * F::ONE, F::MIN);
+                    assert_eq!(F::MAX.checked_mul(F::ONE), Some(F::MAX));
+                    assert_eq!(F::MAX.checked_mul(F::from(2)), None);
+                    assert_eq!(F::MIN.checked_mul(-F::ONE), None);
+                    assert_eq!(F::MAX.saturating_mul(F::from(2)), F::MAX);
+                    assert_eq!(F::MAX.saturating_mul(F::from(-2)), F::MIN);
+                    assert_eq!(F::MIN.saturating_mul(-F::ONE), F::MAX);
+                }
+
+                #[test]
+                fn div() {
+                    let ops = operands();
+                    for &a in &ops {
+                        for &b in ops.iter().step_by(7) {
+                            if b == 0 {
+                                assert_eq!(f(a).checked_div(f(b)), None);
+                                continue
+                            }
+                            // Ties are rounded away from zero, like f64::round.
+                            let want = (a as f64 * ONE / b as f64).round();
+                            let got = f(a) / f(b);
+                            assert_eq!(got.0 as f64, want, "{} / {}", a, b);
+                            assert_eq!(f(a).checked_div(f(b)), Some(got));
+                            assert_eq!(f(a).saturating_div(f(b)), got);
+                            let mut x = f(a);
+                            x /= f(b);
+                            assert_eq!(x, got);
+                        }
+                    }
+                    let two = F::from(2);
+                    assert_eq!(f(1) / two, f(1));
+                    assert_eq!(f(-1) / two, f(-1));
+                    assert_eq!(f(1) / -two, f(-1));
+                    assert_eq!(f(3) / two, f(2));
+                    assert_eq!(f(-3) / two, f(-2));
+
+                    assert_eq!(F::MAX / F::ONE, F::MAX);
+                    assert_eq!(F::MIN / F::ONE, F::MIN);
+                    assert_eq!(F::MAX.checked_div(F::ONE), Some(F::MAX));
+                    assert_eq!(F::MAX.checked_div(F::ZERO), None);
+                    assert_eq!(F::MIN.checked_div(-F::ONE), None);
+                    assert_eq!(F::MAX.checked_div(f(1)), None);
+                    assert_eq!(F::MIN.saturating_div(-F::ONE), F::MAX);
+                    assert_eq!(F::MAX.saturating_div(f(1)), F::MAX);
+                    assert_eq!(F::MAX.saturating_div(f(-1)), F::MIN);
+                }
+
+                #[test]
+                fn neg() {
+                    for v in operands() {
+                        assert_eq!((-f(v)).to_f64(), -(v as f64 / ONE));
+                        assert_eq!(f(v).checked_neg(), Some(-f(v)));
+                    }
+                    assert_eq!(-F::MAX, $name(-$inner::MAX));
+                    assert_eq!(F::MAX.checked_neg(), Some($name(-$inner::MAX)));
+                    assert_eq!(F::MIN.checked_neg(), None);
+                }
+
+                #[test]
+                fn add_sub() {
+                    let ops = operands();
+                    for &a in &ops {
+                        for &b in ops.iter().step_by(7) {
+                            let want = (a as f64 + b as f64) / ONE;
+                            assert_eq!((f(a) + f(b)).to_f64(), want);
+                            assert_eq!(f(a).checked_add(f(b)), Some(f(a) + f(b)));
+                            assert_eq!(f(a).saturating_add(f(b)), f(a) + f(b));
+                            let want = (a as f64 - b as f64) / ONE;
+                            assert_eq!((f(a) - f(b)).to_f64(), want);
+                            assert_eq!(f(a).checked_sub(f(b)), Some(f(a) - f(b)));
+                            assert_eq!(f(a).saturating_sub(f(b)), f(a) - f(b));
+                        }
+                    }
+                    assert_eq!(F::MAX.checked_add(f(1)), None);
+                    assert_eq!(F::MIN.checked_sub(f(1)), None);
+                    assert_eq!(F::MAX.saturating_add(F::ONE), F::MAX);
+                    assert_eq!(F::MIN.saturating_sub(F::ONE), F::MIN);
+                    assert_eq!(F::MIN.saturating_add(F::MAX), f(-1));
+                }
+            }
+        }
+    }
+
+    fixed_tests!(i26_6, I26_6, i32, 6);
+    fixed_tests!(i52_12, I52_12, i64, 12);
+}
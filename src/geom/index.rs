@@ -0,0 +1,620 @@
+// This file contains spatial indexes of rectangles, for hit testing and
+// culling many objects without looking at each of them.
+//
+// Both indexes keep their items in a slab, so that an item keeps its id until
+// it is removed, and ids of removed items are reused. Coordinates are compared
+// in the rectangle's own number type and measured in f64.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::{AnyNum, AnyPoint, AnyRect};
+
+/// Operations shared by `QuadTree` and `Grid`.
+///
+/// Items are rectangles with a value, found by the id that `insert` returns.
+/// Queries report items whose rectangle overlaps or touches the query.
+pub trait SpatialIndex<R: AnyRect, T> {
+    fn insert(&mut self, rect: R, value: T) -> usize;
+    /// Removes the item and returns its rectangle and value.
+    fn remove(&mut self, id: usize) -> Option<(R, T)>;
+    /// Moves the item to `rect`. Returns false if there is no such item.
+    fn update(&mut self, id: usize, rect: R) -> bool;
+    fn get(&self, id: usize) -> Option<(R, &T)>;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool { self.len() == 0 }
+    fn clear(&mut self);
+
+    /// Calls `f` with every item that overlaps or touches `r`.
+    fn query_rect(&self, r: R, f: &mut dyn FnMut(usize, R, &T));
+    /// Calls `f` with every item that contains `p`.
+    fn query_point(&self, p: R::Point, f: &mut dyn FnMut(usize, R, &T));
+    /// Returns the item nearest to `p` and its distance, which is zero for
+    /// items that contain `p`.
+    fn nearest(&self, p: R::Point) -> Option<(usize, f64)>;
+
+    /// Returns the ids of the items that overlap or touch `r`.
+    fn find_rect(&self, r: R) -> Vec<usize> {
+        let mut out = Vec::new();
+        self.query_rect(r, &mut |id, _, _| out.push(id));
+        out
+    }
+
+    /// Returns the ids of the items that contain `p`.
+    fn find_point(&self, p: R::Point) -> Vec<usize> {
+        let mut out = Vec::new();
+        self.query_point(p, &mut |id, _, _| out.push(id));
+        out
+    }
+}
+
+// Slab stores entries by id and reuses the ids of removed ones.
+#[derive(Clone, Debug)]
+struct Slab<E> {
+    entries: Vec<Option<E>>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl<E> Slab<E> {
+    fn new() -> Self { Self { entries: Vec::new(), free: Vec::new(), len: 0 } }
+
+    fn insert(&mut self, e: E) -> usize {
+        self.len += 1;
+        match self.free.pop() {
+            Some(id) => {
+                self.entries[id] = Some(e);
+                id
+            }
+            None => {
+                self.entries.push(Some(e));
+                self.entries.len() - 1
+            }
+        }
+    }
+
+    fn remove(&mut self, id: usize) -> Option<E> {
+        let e = self.entries.get_mut(id)?.take()?;
+        self.free.push(id);
+        self.len -= 1;
+        Some(e)
+    }
+
+    fn get(&self, id: usize) -> Option<&E> { self.entries.get(id)?.as_ref() }
+    fn get_mut(&mut self, id: usize) -> Option<&mut E> { self.entries.get_mut(id)?.as_mut() }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.free.clear();
+        self.len = 0;
+    }
+}
+
+// touches returns whether a and b overlap or share an edge.
+fn touches<R: AnyRect>(a: &R, b: &R) -> bool {
+    a.min().x() <= b.max().x() && b.min().x() <= a.max().x() &&
+    a.min().y() <= b.max().y() && b.min().y() <= a.max().y()
+}
+
+// encloses returns whether b is inside a, edges included.
+fn encloses<R: AnyRect>(a: &R, b: &R) -> bool {
+    a.min().x() <= b.min().x() && b.max().x() <= a.max().x() &&
+    a.min().y() <= b.min().y() && b.max().y() <= a.max().y()
+}
+
+// distance returns the distance from p to the nearest point of r.
+fn distance<R: AnyRect>(r: &R, p: &R::Point) -> f64 {
+    let axis = |v: f64, min: f64, max: f64| if v < min { min - v } else if v > max { v - max } else { 0.0 };
+    let dx = axis(p.x().to_f64(), r.min().x().to_f64(), r.max().x().to_f64());
+    let dy = axis(p.y().to_f64(), r.min().y().to_f64(), r.max().y().to_f64());
+    (dx * dx + dy * dy).sqrt()
+}
+
+// Nearest is a candidate of a nearest neighbour search, ordered so that a
+// BinaryHeap pops the closest one first.
+struct Nearest(f64, usize);
+
+impl PartialEq for Nearest {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+impl Eq for Nearest {}
+impl PartialOrd for Nearest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+impl Ord for Nearest {
+    fn cmp(&self, other: &Self) -> Ordering { other.0.total_cmp(&self.0) }
+}
+
+// MAX_ITEMS is the number of items that a node of a QuadTree holds before it
+// is split, and MAX_DEPTH the depth below which nodes are not split.
+const MAX_ITEMS: usize = 8;
+const MAX_DEPTH: usize = 12;
+
+/// Quadtree over the area `bounds`, which suits items of very different
+/// sizes and uneven distributions.
+///
+/// Each item is kept in the smallest node that holds it whole. Items outside
+/// of the bounds are kept at the root, so they are still found, only slower.
+#[derive(Clone, Debug)]
+pub struct QuadTree<R: AnyRect, T> {
+    items: Slab<(R, T, usize)>,
+    nodes: Vec<Node<R>>,
+}
+
+#[derive(Clone, Debug)]
+struct Node<R> {
+    bounds: R,
+    depth: usize,
+    items: Vec<usize>,
+    children: Option<[usize; 4]>,
+}
+
+impl<R: AnyRect, T> QuadTree<R, T> {
+    pub fn new(bounds: R) -> Self {
+        let root = Node { bounds, depth: 0, items: Vec::new(), children: None };
+        Self { items: Slab::new(), nodes: vec![root] }
+    }
+
+    pub fn bounds(&self) -> R { self.nodes[0].bounds }
+
+    // place adds the item to the deepest node that holds it, starting at
+    // node n, and splits the node if it gets too full.
+    fn place(&mut self, mut n: usize, id: usize, rect: R) {
+        while let Some(children) = self.nodes[n].children {
+            match children.iter().find(|&&c| encloses(&self.nodes[c].bounds, &rect)) {
+                Some(&c) => n = c,
+                None => break,
+            }
+        }
+        self.nodes[n].items.push(id);
+        if let Some(e) = self.items.get_mut(id) {
+            e.2 = n;
+        }
+        let node = &self.nodes[n];
+        if node.children.is_none() && node.items.len() > MAX_ITEMS && node.depth < MAX_DEPTH {
+            self.split(n);
+        }
+    }
+
+    // split gives node n four children and moves down the items that fit in
+    // one of them.
+    fn split(&mut self, n: usize) {
+        let Node { bounds, depth, .. } = self.nodes[n];
+        let (min, max) = (bounds.min(), bounds.max());
+        let mid = |a: R::Num, b: R::Num| R::Num::from_f64((a.to_f64() + b.to_f64()) / 2.0);
+        let (mx, my) = (mid(min.x(), max.x()), mid(min.y(), max.y()));
+        if mx <= min.x() || mx >= max.x() || my <= min.y() || my >= max.y() {
+            return
+        }
+
+        let quads = [
+            (min.x(), min.y(), mx, my),
+            (mx, min.y(), max.x(), my),
+            (min.x(), my, mx, max.y()),
+            (mx, my, max.x(), max.y()),
+        ];
+        let mut children = [0; 4];
+        for (i, &(x0, y0, x1, y1)) in quads.iter().enumerate() {
+            let r = R::from_points(R::Point::new(x0, y0), R::Point::new(x1, y1));
+            children[i] = self.nodes.len();
+            self.nodes.push(Node { bounds: r, depth: depth + 1, items: Vec::new(), children: None });
+        }
+        self.nodes[n].children = Some(children);
+
+        let items = std::mem::take(&mut self.nodes[n].items);
+        for id in items {
+            let rect = self.items.get(id).unwrap().0;
+            self.place(n, id, rect);
+        }
+    }
+}
+
+impl<R: AnyRect, T> SpatialIndex<R, T> for QuadTree<R, T> {
+    fn insert(&mut self, rect: R, value: T) -> usize {
+        let id = self.items.insert((rect, value, 0));
+        self.place(0, id, rect);
+        id
+    }
+
+    fn remove(&mut self, id: usize) -> Option<(R, T)> {
+        let (rect, value, n) = self.items.remove(id)?;
+        let items = &mut self.nodes[n].items;
+        if let Some(i) = items.iter().position(|&v| v == id) {
+            items.swap_remove(i);
+        }
+        Some((rect, value))
+    }
+
+    fn update(&mut self, id: usize, rect: R) -> bool {
+        let n = match self.items.get_mut(id) {
+            Some(e) => {
+                e.0 = rect;
+                e.2
+            }
+            None => return false,
+        };
+        let items = &mut self.nodes[n].items;
+        if let Some(i) = items.iter().position(|&v| v == id) {
+            items.swap_remove(i);
+        }
+        self.place(0, id, rect);
+        true
+    }
+
+    fn get(&self, id: usize) -> Option<(R, &T)> {
+        self.items.get(id).map(|e| (e.0, &e.1))
+    }
+
+    fn len(&self) -> usize { self.items.len }
+
+    fn clear(&mut self) {
+        self.items.clear();
+        self.nodes.truncate(1);
+        self.nodes[0].items.clear();
+        self.nodes[0].children = None;
+    }
+
+    fn query_rect(&self, r: R, f: &mut dyn FnMut(usize, R, &T)) {
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            for &id in &node.items {
+                let e = self.items.get(id).unwrap();
+                if touches(&e.0, &r) {
+                    f(id, e.0, &e.1);
+                }
+            }
+            if let Some(children) = node.children {
+                stack.extend(children.iter().filter(|&&c| touches(&self.nodes[c].bounds, &r)));
+            }
+        }
+    }
+
+    fn query_point(&self, p: R::Point, f: &mut dyn FnMut(usize, R, &T)) {
+        let mut n = 0;
+        loop {
+            let node = &self.nodes[n];
+            for &id in &node.items {
+                let e = self.items.get(id).unwrap();
+                if e.0.contains_point(&p) {
+                    f(id, e.0, &e.1);
+                }
+            }
+            // Items that contain p are in nodes whose bounds touch p, and
+            // only one child can hold it inside.
+            let next = node.children.and_then(|children| {
+                children.iter().cloned().find(|&c| self.nodes[c].bounds.contains_point(&p))
+            });
+            match next {
+                Some(c) => n = c,
+                None => break,
+            }
+        }
+    }
+
+    fn nearest(&self, p: R::Point) -> Option<(usize, f64)> {
+        let mut best: Option<(usize, f64)> = None;
+        // The root may hold items outside of its bounds, so it is searched
+        // whatever its distance.
+        let mut heap = BinaryHeap::new();
+        heap.push(Nearest(0.0, 0));
+        while let Some(Nearest(d, n)) = heap.pop() {
+            if best.is_some_and(|(_, b)| d > b) {
+                break
+            }
+            let node = &self.nodes[n];
+            for &id in &node.items {
+                let d = distance(&self.items.get(id).unwrap().0, &p);
+                if best.is_none_or(|(_, b)| d < b) {
+                    best = Some((id, d));
+                }
+            }
+            if let Some(children) = node.children {
+                for &c in &children {
+                    heap.push(Nearest(distance(&self.nodes[c].bounds, &p), c));
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Uniform grid of square cells, which suits many items of about the same
+/// size, like the objects of a tile map.
+///
+/// Cells are only allocated where there are items, so the grid has no
+/// bounds. Items are listed in every cell they touch, so cells should be
+/// about as large as typical items.
+#[derive(Clone, Debug)]
+pub struct Grid<R: AnyRect, T> {
+    items: Slab<(R, T)>,
+    cells: HashMap<(isize, isize), Vec<usize>>,
+    size: f64,
+    // used is the range of cells that ever had items, which bounds the
+    // nearest neighbour search.
+    used: Option<[isize; 4]>,
+}
+
+impl<R: AnyRect, T> Grid<R, T> {
+    /// Returns an empty grid of cells of `cell_size` by `cell_size`, with a
+    /// corner at the origin.
+    pub fn new(cell_size: R::Num) -> Self {
+        let size = cell_size.to_f64();
+        assert!(size > 0.0, "cell size must be positive");
+        Self { items: Slab::new(), cells: HashMap::new(), size, used: None }
+    }
+
+    fn cell(&self, x: R::Num, y: R::Num) -> (isize, isize) {
+        ((x.to_f64() / self.size).floor() as isize, (y.to_f64() / self.size).floor() as isize)
+    }
+
+    // cells returns the range of cells that r touches, as [x0, y0, x1, y1]
+    // with both ends included.
+    fn cells(&self, r: &R) -> [isize; 4] {
+        let (x0, y0) = self.cell(r.min().x(), r.min().y());
+        let (x1, y1) = self.cell(r.max().x(), r.max().y());
+        [x0, y0, x1.max(x0), y1.max(y0)]
+    }
+
+    fn link(&mut self, id: usize, r: &R) {
+        let [x0, y0, x1, y1] = self.cells(r);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                self.cells.entry((x, y)).or_default().push(id);
+            }
+        }
+        self.used = Some(match self.used {
+            Some([ux0, uy0, ux1, uy1]) => [ux0.min(x0), uy0.min(y0), ux1.max(x1), uy1.max(y1)],
+            None => [x0, y0, x1, y1],
+        });
+    }
+
+    fn unlink(&mut self, id: usize, r: &R) {
+        let [x0, y0, x1, y1] = self.cells(r);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                if let Some(ids) = self.cells.get_mut(&(x, y)) {
+                    if let Some(i) = ids.iter().position(|&v| v == id) {
+                        ids.swap_remove(i);
+                    }
+                    if ids.is_empty() {
+                        self.cells.remove(&(x, y));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<R: AnyRect, T> SpatialIndex<R, T> for Grid<R, T> {
+    fn insert(&mut self, rect: R, value: T) -> usize {
+        let id = self.items.insert((rect, value));
+        self.link(id, &rect);
+        id
+    }
+
+    fn remove(&mut self, id: usize) -> Option<(R, T)> {
+        let (rect, value) = self.items.remove(id)?;
+        self.unlink(id, &rect);
+        Some((rect, value))
+    }
+
+    fn update(&mut self, id: usize, rect: R) -> bool {
+        let old = match self.items.get_mut(id) {
+            Some(e) => std::mem::replace(&mut e.0, rect),
+            None => return false,
+        };
+        if self.cells(&old) != self.cells(&rect) {
+            self.unlink(id, &old);
+            self.link(id, &rect);
+        }
+        true
+    }
+
+    fn get(&self, id: usize) -> Option<(R, &T)> {
+        self.items.get(id).map(|e| (e.0, &e.1))
+    }
+
+    fn len(&self) -> usize { self.items.len }
+
+    fn clear(&mut self) {
+        self.items.clear();
+        self.cells.clear();
+        self.used = None;
+    }
+
+    fn query_rect(&self, r: R, f: &mut dyn FnMut(usize, R, &T)) {
+        let [x0, y0, x1, y1] = self.cells(&r);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                for &id in self.cells.get(&(x, y)).into_iter().flatten() {
+                    let e = self.items.get(id).unwrap();
+                    if !touches(&e.0, &r) {
+                        continue
+                    }
+                    // An item in several cells is reported only from the cell
+                    // that holds the min corner of its part in r.
+                    let cx = if e.0.min().x() > r.min().x() { e.0.min().x() } else { r.min().x() };
+                    let cy = if e.0.min().y() > r.min().y() { e.0.min().y() } else { r.min().y() };
+                    if self.cell(cx, cy) == (x, y) {
+                        f(id, e.0, &e.1);
+                    }
+                }
+            }
+        }
+    }
+
+    fn query_point(&self, p: R::Point, f: &mut dyn FnMut(usize, R, &T)) {
+        for &id in self.cells.get(&self.cell(p.x(), p.y())).into_iter().flatten() {
+            let e = self.items.get(id).unwrap();
+            if e.0.contains_point(&p) {
+                f(id, e.0, &e.1);
+            }
+        }
+    }
+
+    fn nearest(&self, p: R::Point) -> Option<(usize, f64)> {
+        let [ux0, uy0, ux1, uy1] = self.used?;
+        let (cx, cy) = self.cell(p.x(), p.y());
+        // Search rings of cells around the cell of p, from the first one that
+        // reaches the used cells, and only where they overlap them. Every
+        // cell of ring k is at least (k - 1) cells away from p.
+        let first = (ux0 - cx).max(cx - ux1).max(uy0 - cy).max(cy - uy1).max(0);
+        let mut best: Option<(usize, f64)> = None;
+        for k in first.. {
+            if best.is_some_and(|(_, b)| b <= (k as f64 - 1.0) * self.size) {
+                break
+            }
+            if cx - k < ux0 && cy - k < uy0 && cx + k > ux1 && cy + k > uy1 {
+                break
+            }
+            let (x0, x1) = ((cx - k).max(ux0), (cx + k).min(ux1));
+            for y in (cy - k).max(uy0)..=(cy + k).min(uy1) {
+                let edge = y == cy - k || y == cy + k;
+                // Rows at the top and bottom of the ring are whole, the
+                // others only have their two ends.
+                let (rx0, rx1) = if edge { (x0, x1) } else { (1, 0) };
+                let sides = [cx - k, cx + k];
+                let sides = sides.iter().cloned().filter(|&x| !edge && x >= ux0 && x <= ux1);
+                for x in (rx0..=rx1).chain(sides) {
+                    for &id in self.cells.get(&(x, y)).into_iter().flatten() {
+                        let d = distance(&self.items.get(id).unwrap().0, &p);
+                        if best.is_none_or(|(_, b)| d < b) {
+                            best = Some((id, d));
+                        }
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{Point, Rectangle};
+
+    // rand is xorshift32, to make items without a dependency.
+    fn rand(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    // coord returns a coordinate from -16 to 80, around the quadtree bounds
+    // of 0 to 64, often on the edge of a grid cell.
+    fn coord(state: &mut u32) -> isize {
+        let v = (rand(state) % 97) as isize - 16;
+        if rand(state).is_multiple_of(3) { v & !7 } else { v }
+    }
+
+    fn rect(state: &mut u32) -> Rectangle {
+        let (x, y) = (coord(state), coord(state));
+        let (w, h) = ((rand(state) % 20) as isize, (rand(state) % 20) as isize);
+        Rectangle::new(x, y, x + w, y + h)
+    }
+
+    fn point(state: &mut u32) -> Point {
+        Point { x: coord(state), y: coord(state) }
+    }
+
+    // check runs random inserts, removes and updates on index and on a
+    // vector of the items by id, and compares the queries with a linear scan
+    // of the vector.
+    fn check<I: SpatialIndex<Rectangle, usize>>(mut index: I) {
+        let mut state = 5;
+        let mut items: Vec<Option<Rectangle>> = Vec::new();
+        let mut freed: Vec<usize> = Vec::new();
+        for step in 0..600 {
+            let live: Vec<usize> = (0..items.len()).filter(|&id| items[id].is_some()).collect();
+            match rand(&mut state) % 4 {
+                0 | 1 => {
+                    let r = rect(&mut state);
+                    let id = index.insert(r, step);
+                    // Ids of removed items are reused before new ones.
+                    match freed.pop() {
+                        Some(want) => assert_eq!(id, want),
+                        None => assert_eq!(id, items.len()),
+                    }
+                    if id == items.len() {
+                        items.push(None);
+                    }
+                    items[id] = Some(r);
+                }
+                2 if !live.is_empty() => {
+                    let id = live[rand(&mut state) as usize % live.len()];
+                    assert_eq!(index.remove(id).map(|(r, _)| r), items[id]);
+                    assert!(index.remove(id).is_none());
+                    items[id] = None;
+                    freed.push(id);
+                }
+                3 if !live.is_empty() => {
+                    let id = live[rand(&mut state) as usize % live.len()];
+                    let r = rect(&mut state);
+                    assert!(index.update(id, r));
+                    items[id] = Some(r);
+                }
+                _ => (),
+            }
+            assert_eq!(index.len(), items.iter().flatten().count());
+            assert!(!index.update(items.len(), Rectangle::default()));
+
+            for _ in 0..4 {
+                let q = rect(&mut state);
+                let mut got = index.find_rect(q);
+                got.sort_unstable();
+                let want: Vec<usize> = (0..items.len()).filter(|&id| items[id].is_some_and(|r| {
+                    r.min.x <= q.max.x && q.min.x <= r.max.x && r.min.y <= q.max.y && q.min.y <= r.max.y
+                })).collect();
+                assert_eq!(got, want, "find_rect({:?})", q);
+
+                let p = point(&mut state);
+                let mut got = index.find_point(p);
+                got.sort_unstable();
+                let want: Vec<usize> = (0..items.len())
+                    .filter(|&id| items[id].is_some_and(|r| p.in_rect(&r)))
+                    .collect();
+                assert_eq!(got, want, "find_point({:?})", p);
+
+                let dist = |r: &Rectangle| {
+                    let dx = (r.min.x - p.x).max(p.x - r.max.x).max(0) as f64;
+                    let dy = (r.min.y - p.y).max(p.y - r.max.y).max(0) as f64;
+                    (dx * dx + dy * dy).sqrt()
+                };
+                let want = items.iter().flatten().map(dist).min_by(f64::total_cmp);
+                let got = index.nearest(p);
+                assert_eq!(got.map(|(_, d)| d), want, "nearest({:?})", p);
+                if let Some((id, d)) = got {
+                    assert_eq!(items[id].as_ref().map(dist), Some(d));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn quadtree() {
+        check(QuadTree::<Rectangle, usize>::new(Rectangle::new(0, 0, 64, 64)));
+    }
+
+    #[test]
+    fn grid() {
+        check(Grid::<Rectangle, usize>::new(8));
+    }
+
+    #[test]
+    fn cell_edges() {
+        // An item that ends on a cell edge touches the next cell, and a
+        // point on the edge is only in the item that starts there.
+        let mut grid = Grid::new(8);
+        let a = grid.insert(Rectangle::new(0, 0, 8, 8), ());
+        let b = grid.insert(Rectangle::new(8, 0, 16, 8), ());
+        assert_eq!(grid.find_point(Point { x: 8, y: 4 }), [b]);
+        assert_eq!(grid.find_point(Point { x: 7, y: 4 }), [a]);
+        let mut got = grid.find_rect(Rectangle::new(8, 8, 8, 8));
+        got.sort_unstable();
+        assert_eq!(got, [a, b]);
+        assert_eq!(grid.nearest(Point { x: 8, y: 20 }).map(|(_, d)| d), Some(12.0));
+    }
+}
@@ -0,0 +1,416 @@
+// This file contains regions: sets of pixels described by rectangles, as
+// pixman and X11 do.
+//
+// The rectangles are kept in bands. A band is a run of rows that holds the
+// same spans, stored as rectangles of the band's height sorted by x, which
+// neither overlap nor touch. Bands are sorted by y, and two bands that touch
+// have different spans, so every set of pixels has exactly one form.
+
+use crate::image::{Point, Rectangle};
+
+/// Set of pixels, as non-overlapping rectangles.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Region {
+    rects: Vec<Rectangle>,
+    extents: Rectangle,
+}
+
+/// Rows of a region that hold the same spans, as returned by `Region::bands`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Band<'a> {
+    pub y0: isize,
+    pub y1: isize,
+    /// The spans of the band, sorted by x, as rectangles from y0 to y1.
+    pub rects: &'a [Rectangle],
+}
+
+impl From<Rectangle> for Region {
+    fn from(r: Rectangle) -> Self {
+        if r.is_empty() {
+            Self::new()
+        } else {
+            Self { rects: vec![r], extents: r }
+        }
+    }
+}
+
+impl Region {
+    pub fn new() -> Self { Self::default() }
+
+    /// Returns the union of the rectangles.
+    pub fn from_rects<I: IntoIterator<Item=Rectangle>>(rects: I) -> Self {
+        let mut rects: Vec<Region> = rects.into_iter().map(Region::from).collect();
+        // Merging in pairs keeps the bands of the operands small.
+        while rects.len() > 1 {
+            let mut next = Vec::with_capacity(rects.len().div_ceil(2));
+            for pair in rects.chunks(2) {
+                next.push(match pair {
+                    [a, b] => a.union(b),
+                    [a] => a.clone(),
+                    _ => unreachable!(),
+                });
+            }
+            rects = next;
+        }
+        rects.pop().unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool { self.rects.is_empty() }
+
+    /// Returns the smallest rectangle that contains the region.
+    pub fn extents(&self) -> Rectangle { self.extents }
+
+    /// Returns the rectangles of the region, band after band.
+    pub fn rects(&self) -> &[Rectangle] { &self.rects }
+
+    /// Returns the number of pixels in the region.
+    pub fn area(&self) -> isize {
+        self.rects.iter().map(|r| r.dx() * r.dy()).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.rects.clear();
+        self.extents = Rectangle::default();
+    }
+
+    /// Returns the bands of the region, from top to bottom.
+    pub fn bands(&self) -> impl Iterator<Item = Band<'_>> {
+        let mut rest = &self.rects[..];
+        std::iter::from_fn(move || {
+            let first = rest.first()?;
+            let n = rest.iter().take_while(|r| r.min.y == first.min.y).count();
+            let (rects, tail) = rest.split_at(n);
+            rest = tail;
+            Some(Band { y0: first.min.y, y1: first.max.y, rects })
+        })
+    }
+
+    /// Returns whether the pixel p is in the region.
+    pub fn contains_point(&self, p: Point) -> bool {
+        if !p.in_rect(&self.extents) {
+            return false
+        }
+        // The first rectangle of the band holding p, if any.
+        let i = self.rects.partition_point(|r| r.max.y <= p.y);
+        let band = &self.rects[i..];
+        let band = &band[..band.iter().take_while(|r| r.min.y == band[0].min.y).count()];
+        if band.first().is_none_or(|r| r.min.y > p.y) {
+            return false
+        }
+        let j = band.partition_point(|r| r.max.x <= p.x);
+        j < band.len() && band[j].min.x <= p.x
+    }
+
+    /// Returns whether every pixel of r is in the region.
+    pub fn contains_rect(&self, r: Rectangle) -> bool {
+        r.is_empty() || Region::from(r).subtract(self).is_empty()
+    }
+
+    /// Returns whether some pixel of r is in the region.
+    pub fn overlaps(&self, r: Rectangle) -> bool {
+        self.extents.overlaps(&r) && self.rects.iter().any(|s| s.overlaps(&r))
+    }
+
+    /// Returns the parts of r that are in the region, for drawing clipped
+    /// to it one rectangle at a time.
+    pub fn clip(&self, r: Rectangle) -> impl Iterator<Item = Rectangle> + '_ {
+        let rects = if self.extents.overlaps(&r) { &self.rects[..] } else { &[] };
+        rects.iter()
+            .skip_while(move |s| s.max.y <= r.min.y)
+            .take_while(move |s| s.min.y < r.max.y)
+            .filter_map(move |s| s.maybe_intersect(r))
+    }
+
+    /// Moves the region by (dx, dy).
+    pub fn translate(&mut self, dx: isize, dy: isize) {
+        for r in &mut self.rects {
+            *r = r.translate(dx, dy);
+        }
+        if !self.is_empty() {
+            self.extents = self.extents.translate(dx, dy);
+        }
+    }
+
+    /// Returns the pixels in either region.
+    pub fn union(&self, other: &Region) -> Region {
+        if self.is_empty() { return other.clone() }
+        if other.is_empty() { return self.clone() }
+        combine(self, other, |a, b| a || b)
+    }
+
+    /// Returns the pixels in both regions.
+    pub fn intersect(&self, other: &Region) -> Region {
+        if !self.extents.overlaps(&other.extents) {
+            return Region::new()
+        }
+        combine(self, other, |a, b| a && b)
+    }
+
+    /// Returns the pixels of the region that are not in `other`.
+    pub fn subtract(&self, other: &Region) -> Region {
+        if !self.extents.overlaps(&other.extents) {
+            return self.clone()
+        }
+        combine(self, other, |a, b| a && !b)
+    }
+
+    /// Returns the pixels in exactly one of the regions.
+    pub fn xor(&self, other: &Region) -> Region {
+        combine(self, other, |a, b| a != b)
+    }
+
+    /// Adds the pixels of r to the region.
+    pub fn union_rect(&mut self, r: Rectangle) {
+        // Drawing mostly adds to areas that are already damaged, which one
+        // rectangle usually covers.
+        if !r.is_empty() && !self.rects.iter().any(|s| r.in_rect(s)) {
+            *self = self.union(&Region::from(r));
+        }
+    }
+
+    /// Removes the pixels of r from the region.
+    pub fn subtract_rect(&mut self, r: Rectangle) {
+        if self.extents.overlaps(&r) {
+            *self = self.subtract(&Region::from(r));
+        }
+    }
+
+    /// Keeps only the pixels of the region that are in r.
+    pub fn intersect_rect(&mut self, r: Rectangle) {
+        if !self.extents.in_rect(&r) {
+            *self = self.intersect(&Region::from(r));
+        }
+    }
+
+    // push_band adds the band from y0 to y1 with the given spans, which must
+    // be below the bands so far, and merges it into the last band if they
+    // touch and have the same spans.
+    fn push_band(&mut self, last: &mut usize, y0: isize, y1: isize, spans: &[(isize, isize)]) {
+        if spans.is_empty() {
+            return
+        }
+        let prev = &mut self.rects[*last..];
+        let same = !prev.is_empty() && prev[0].max.y == y0 && prev.len() == spans.len() &&
+            prev.iter().zip(spans).all(|(r, &(x0, x1))| r.min.x == x0 && r.max.x == x1);
+        if same {
+            for r in prev {
+                r.max.y = y1;
+            }
+        } else {
+            *last = self.rects.len();
+            for &(x0, x1) in spans {
+                self.rects.push(Rectangle { min: Point { x: x0, y: y0 }, max: Point { x: x1, y: y1 } });
+            }
+        }
+    }
+
+    fn update_extents(&mut self) {
+        self.extents = match (self.rects.first(), self.rects.last()) {
+            (Some(first), Some(last)) => {
+                let x0 = self.rects.iter().map(|r| r.min.x).min().unwrap();
+                let x1 = self.rects.iter().map(|r| r.max.x).max().unwrap();
+                Rectangle { min: Point { x: x0, y: first.min.y }, max: Point { x: x1, y: last.max.y } }
+            }
+            _ => Rectangle::default(),
+        };
+    }
+}
+
+// combine returns the pixels for which op gives true, from whether they are
+// in a and in b.
+//
+// Both regions are cut at every band edge of either one, and the spans of
+// each piece are combined by sweeping over their ends.
+fn combine(a: &Region, b: &Region, op: impl Fn(bool, bool) -> bool) -> Region {
+    let (ba, bb): (Vec<Band>, Vec<Band>) = (a.bands().collect(), b.bands().collect());
+    let mut ys: Vec<isize> = ba.iter().chain(&bb).flat_map(|b| vec![b.y0, b.y1]).collect();
+    ys.sort_unstable();
+    ys.dedup();
+
+    let mut out = Region::new();
+    let mut last = 0;
+    let (mut ia, mut ib) = (0, 0);
+    let mut spans = Vec::new();
+    for w in ys.windows(2) {
+        let (y0, y1) = (w[0], w[1]);
+        while ia < ba.len() && ba[ia].y1 <= y0 { ia += 1 }
+        while ib < bb.len() && bb[ib].y1 <= y0 { ib += 1 }
+        let sa = ba.get(ia).filter(|b| b.y0 <= y0).map_or(&[][..], |b| b.rects);
+        let sb = bb.get(ib).filter(|b| b.y0 <= y0).map_or(&[][..], |b| b.rects);
+
+        spans.clear();
+        combine_spans(sa, sb, &op, &mut spans);
+        out.push_band(&mut last, y0, y1, &spans);
+    }
+    out.update_extents();
+    out
+}
+
+// combine_spans adds to out the spans for which op gives true, from whether
+// they are in a span of a and of b.
+fn combine_spans(a: &[Rectangle], b: &[Rectangle], op: &impl Fn(bool, bool) -> bool, out: &mut Vec<(isize, isize)>) {
+    let mut xs: Vec<isize> = a.iter().chain(b).flat_map(|r| vec![r.min.x, r.max.x]).collect();
+    xs.sort_unstable();
+    xs.dedup();
+
+    let (mut ia, mut ib) = (0, 0);
+    for w in xs.windows(2) {
+        let (x0, x1) = (w[0], w[1]);
+        while ia < a.len() && a[ia].max.x <= x0 { ia += 1 }
+        while ib < b.len() && b[ib].max.x <= x0 { ib += 1 }
+        let in_a = ia < a.len() && a[ia].min.x <= x0;
+        let in_b = ib < b.len() && b[ib].min.x <= x0;
+        if !op(in_a, in_b) {
+            continue
+        }
+        match out.last_mut() {
+            Some(last) if last.1 == x0 => last.1 = x1,
+            _ => out.push((x0, x1)),
+        }
+    }
+}
+
+impl std::ops::BitOr for &Region {
+    type Output = Region;
+    fn bitor(self, other: &Region) -> Region { self.union(other) }
+}
+
+impl std::ops::BitAnd for &Region {
+    type Output = Region;
+    fn bitand(self, other: &Region) -> Region { self.intersect(other) }
+}
+
+impl std::ops::Sub for &Region {
+    type Output = Region;
+    fn sub(self, other: &Region) -> Region { self.subtract(other) }
+}
+
+impl std::ops::BitXor for &Region {
+    type Output = Region;
+    fn bitxor(self, other: &Region) -> Region { self.xor(other) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: isize = 20;
+
+    // rand is xorshift32, to make regions without a dependency.
+    fn rand(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    fn random_rects(state: &mut u32, n: usize) -> Vec<Rectangle> {
+        (0..n).map(|_| {
+            let mut c = || (rand(state) % (N as u32 - 2)) as isize + 1;
+            Rectangle::new(c(), c(), c(), c()).canon()
+        }).collect()
+    }
+
+    // pixels returns whether each pixel of the N by N square is in rects.
+    fn pixels(rects: &[Rectangle]) -> Vec<bool> {
+        let mut out = vec![false; (N * N) as usize];
+        for r in rects {
+            for y in r.min.y..r.max.y {
+                for x in r.min.x..r.max.x {
+                    out[(y * N + x) as usize] = true;
+                }
+            }
+        }
+        out
+    }
+
+    // check_form checks that the rectangles of r are in the canonical band
+    // form and that the extents are tight.
+    fn check_form(r: &Region) {
+        let bands: Vec<Band> = r.bands().collect();
+        for b in &bands {
+            assert!(b.y0 < b.y1 && !b.rects.is_empty());
+            for s in b.rects {
+                assert!(s.min.y == b.y0 && s.max.y == b.y1 && s.min.x < s.max.x);
+            }
+            for w in b.rects.windows(2) {
+                assert!(w[0].max.x < w[1].min.x, "spans touch in {:?}", b);
+            }
+        }
+        for w in bands.windows(2) {
+            assert!(w[0].y1 <= w[1].y0);
+            assert!(w[0].y1 < w[1].y0 || w[0].rects.len() != w[1].rects.len() ||
+                w[0].rects.iter().zip(w[1].rects).any(|(a, b)| a.min.x != b.min.x || a.max.x != b.max.x),
+                "bands {:?} and {:?} should be merged", w[0], w[1]);
+        }
+        let want = r.rects().iter().fold(Rectangle::default(), |e, s| e.union(*s));
+        assert_eq!(r.extents(), want);
+        assert_eq!(r.area(), pixels(r.rects()).iter().filter(|&&p| p).count() as isize);
+    }
+
+    #[test]
+    fn operations() {
+        let mut state = 3;
+        for n in 0..200 {
+            let ra = random_rects(&mut state, n % 5);
+            let rb = random_rects(&mut state, n % 7);
+            let (a, b) = (Region::from_rects(ra.clone()), Region::from_rects(rb.clone()));
+            let (pa, pb) = (pixels(&ra), pixels(&rb));
+            assert_eq!(pixels(a.rects()), pa);
+            check_form(&a);
+
+            for (got, op) in [
+                (&a | &b, (|a, b| a || b) as fn(bool, bool) -> bool),
+                (&a & &b, |a, b| a && b),
+                (&a - &b, |a, b| a && !b),
+                (&a ^ &b, |a, b| a != b),
+            ] {
+                check_form(&got);
+                let want: Vec<bool> = pa.iter().zip(&pb).map(|(&a, &b)| op(a, b)).collect();
+                assert_eq!(pixels(got.rects()), want, "{:?} {:?}", ra, rb);
+            }
+            assert_eq!(&a | &b, &b | &a);
+            assert_eq!(&a & &b, &b & &a);
+            assert_eq!(&a ^ &b, &b ^ &a);
+            // The form only depends on the pixels.
+            assert_eq!(&(&a - &b) | &(&a & &b), a);
+        }
+    }
+
+    #[test]
+    fn band_merging() {
+        // Two rectangles stacked with the same spans make one.
+        let r = Region::from_rects(vec![Rectangle::new(0, 0, 4, 2), Rectangle::new(0, 2, 4, 5)]);
+        assert_eq!(r.rects(), &[Rectangle::new(0, 0, 4, 5)]);
+        // Side by side, they make one span.
+        let r = Region::from_rects(vec![Rectangle::new(0, 0, 4, 2), Rectangle::new(4, 0, 6, 2)]);
+        assert_eq!(r.rects(), &[Rectangle::new(0, 0, 6, 2)]);
+        // Bands split by another rectangle are merged again when it goes.
+        let a = Region::from(Rectangle::new(0, 0, 4, 6));
+        let b = Region::from(Rectangle::new(6, 2, 8, 4));
+        let u = &a | &b;
+        assert_eq!(u.rects().len(), 4);
+        assert_eq!(&u - &b, a);
+        // A gap between bands with the same spans keeps them apart.
+        let r = Region::from_rects(vec![Rectangle::new(0, 0, 4, 2), Rectangle::new(0, 3, 4, 5)]);
+        assert_eq!(r.rects().len(), 2);
+    }
+
+    #[test]
+    fn contains_point() {
+        let mut state = 11;
+        for n in 0..100 {
+            let rects = random_rects(&mut state, n % 6);
+            let r = Region::from_rects(rects.clone());
+            let want = pixels(&rects);
+            // The whole square and one pixel around it, which covers the
+            // edges of every band and span.
+            for y in -1..=N {
+                for x in -1..=N {
+                    let inside = (0..N).contains(&x) && (0..N).contains(&y) && want[(y * N + x) as usize];
+                    assert_eq!(r.contains_point(Point { x, y }), inside, "({}, {}) in {:?}", x, y, rects);
+                }
+            }
+        }
+    }
+}
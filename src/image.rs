@@ -1,34 +1,331 @@
-#[derive(Clone, Copy)]
+use crate::geom::{AnyPoint, AnyRect};
+
+/// Integer coordinate pair, in pixels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
 pub struct Point {
     pub x: isize,
     pub y: isize,
 }
 
-#[derive(Clone, Copy)]
+/// Integer rectangle, in pixels.
+/// The Min bound is inclusive and the Max bound is exclusive.
+/// It is well-formed if Min.X <= Max.X and likewise for Y.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
 pub struct Rectangle {
     pub min: Point,
     pub max: Point,
 }
 
-impl Rectangle {
-    pub fn from_size(w: isize, h: isize) -> Self {
-        Self {
-            min: Point { x: 0, y: 0 },
-            max: Point { x: w, y: h },
+/// Floating-point coordinate pair.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct PointF {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Floating-point rectangle, with the same bounds as `Rectangle`.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct RectangleF {
+    pub min: PointF,
+    pub max: PointF,
+}
+
+/// Coordinate pair of 32-bit integers, for data stored in a compact form.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Point32 {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Rectangle of 32-bit integers, with the same bounds as `Rectangle`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Rectangle32 {
+    pub min: Point32,
+    pub max: Point32,
+}
+
+/// Double precision coordinate pair, for world coordinates.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct PointF64 {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Double precision rectangle, with the same bounds as `Rectangle`.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct RectangleF64 {
+    pub min: PointF64,
+    pub max: PointF64,
+}
+
+macro_rules! impl_rect {
+    ($point:ident, $rect:ident, $num:ty, $two:expr) => {
+        impl $point {
+            pub const fn new(x: $num, y: $num) -> Self { Self { x, y } }
+
+            /// Returns whether p is in r.
+            pub fn in_rect(&self, r: &$rect) -> bool {
+                r.min.x <= self.x && self.x < r.max.x &&
+                r.min.y <= self.y && self.y < r.max.y
+            }
+        }
+
+        impl std::ops::Add for $point {
+            type Output = Self;
+            fn add(self, p: Self) -> Self { Self { x: self.x + p.x, y: self.y + p.y } }
+        }
+
+        impl std::ops::Sub for $point {
+            type Output = Self;
+            fn sub(self, p: Self) -> Self { Self { x: self.x - p.x, y: self.y - p.y } }
+        }
+
+        impl std::ops::Mul<$num> for $point {
+            type Output = Self;
+            fn mul(self, k: $num) -> Self { Self { x: self.x * k, y: self.y * k } }
+        }
+
+        impl std::ops::Div<$num> for $point {
+            type Output = Self;
+            fn div(self, k: $num) -> Self { Self { x: self.x / k, y: self.y / k } }
+        }
+
+        impl std::ops::Neg for $point {
+            type Output = Self;
+            fn neg(self) -> Self { Self { x: -self.x, y: -self.y } }
+        }
+
+        impl std::ops::AddAssign for $point {
+            fn add_assign(&mut self, p: Self) { *self = *self + p }
+        }
+
+        impl std::ops::SubAssign for $point {
+            fn sub_assign(&mut self, p: Self) { *self = *self - p }
+        }
+
+        impl AnyPoint for $point {
+            type Num = $num;
+            fn new(x: $num, y: $num) -> Self { $point::new(x, y) }
+            fn x(&self) -> $num { self.x }
+            fn y(&self) -> $num { self.y }
+            fn mul(self, rhs: $num) -> Self { self * rhs }
+            fn div(self, rhs: $num) -> Self { self / rhs }
+        }
+
+        impl $rect {
+            /// Returns the rectangle with the corners (x0, y0) and (x1, y1),
+            /// swapped if necessary so that it is well-formed.
+            pub fn new(x0: $num, y0: $num, x1: $num, y1: $num) -> Self {
+                let (x0, x1) = if x0 > x1 { (x1, x0) } else { (x0, x1) };
+                let (y0, y1) = if y0 > y1 { (y1, y0) } else { (y0, y1) };
+                Self { min: $point { x: x0, y: y0 }, max: $point { x: x1, y: y1 } }
+            }
+
+            pub fn from_size(w: $num, h: $num) -> Self {
+                Self {
+                    min: $point::default(),
+                    max: $point { x: w, y: h },
+                }
+            }
+
+            pub fn dx(&self) -> $num { self.max.x - self.min.x }
+            pub fn dy(&self) -> $num { self.max.y - self.min.y }
+
+            /// Returns the width and height of the rectangle.
+            pub fn size(&self) -> $point { self.max - self.min }
+
+            /// Returns the point halfway between min and max.
+            pub fn center(&self) -> $point { (self.min + self.max) / $two }
+
+            /// Returns the well-formed version of the rectangle.
+            pub fn canon(self) -> Self { Self::new(self.min.x, self.min.y, self.max.x, self.max.y) }
+
+            /// Returns the rectangle translated by (dx, dy).
+            pub fn translate(self, dx: $num, dy: $num) -> Self { self + $point { x: dx, y: dy } }
+
+            /// Returns the rectangle inset by n, which may be negative. If either
+            /// of the rectangle's dimensions is less than 2*n then an empty
+            /// rectangle near the center will be returned.
+            pub fn inset(self, n: $num) -> Self {
+                let mut r = self;
+                if r.dx() < $two * n {
+                    r.min.x = (r.min.x + r.max.x) / $two;
+                    r.max.x = r.min.x;
+                } else {
+                    r.min.x += n;
+                    r.max.x -= n;
+                }
+                if r.dy() < $two * n {
+                    r.min.y = (r.min.y + r.max.y) / $two;
+                    r.max.y = r.min.y;
+                } else {
+                    r.min.y += n;
+                    r.max.y -= n;
+                }
+                r
+            }
+
+            /// Returns whether the rectangle contains no points.
+            pub fn is_empty(&self) -> bool {
+                self.min.x >= self.max.x || self.min.y >= self.max.y
+            }
+
+            /// Returns whether p is in the rectangle.
+            pub fn contains(&self, p: $point) -> bool { p.in_rect(self) }
+
+            /// Returns whether every point in r is in s.
+            pub fn in_rect(&self, s: &Self) -> bool {
+                if self.is_empty() {
+                    true
+                } else {
+                    // NOTE that r.Max is an exclusive bound for r, so that r.In(s)
+                    // does not require that r.Max.In(s).
+                    s.min.x <= self.min.x && self.max.x <= s.max.x &&
+                    s.min.y <= self.min.y && self.max.y <= s.max.y
+                }
+            }
+
+            /// Returns whether r and s have a non-empty intersection.
+            pub fn overlaps(&self, s: &Self) -> bool {
+                !self.is_empty() && !s.is_empty() &&
+                self.min.x < s.max.x && s.min.x < self.max.x &&
+                self.min.y < s.max.y && s.min.y < self.max.y
+            }
+
+            /// Returns the largest rectangle contained by both r and s.
+            /// If the two rectangles do not overlap then the zero rectangle will be returned.
+            pub fn intersect(self, s: Self) -> Self {
+                self.maybe_intersect(s).unwrap_or_default()
+            }
+
+            /// Returns the largest rectangle contained by both r and s,
+            /// or `None` if the two rectangles do not overlap.
+            pub fn maybe_intersect(self, s: Self) -> Option<Self> {
+                let mut r = self;
+                if r.min.x < s.min.x { r.min.x = s.min.x }
+                if r.min.y < s.min.y { r.min.y = s.min.y }
+                if r.max.x > s.max.x { r.max.x = s.max.x }
+                if r.max.y > s.max.y { r.max.y = s.max.y }
+                if r.is_empty() { None } else { Some(r) }
+            }
+
+            /// Returns the smallest rectangle that contains both r and s.
+            pub fn union(self, s: Self) -> Self {
+                if self.is_empty() { return s }
+                if s.is_empty() { return self }
+                let mut r = self;
+                if r.min.x > s.min.x { r.min.x = s.min.x }
+                if r.min.y > s.min.y { r.min.y = s.min.y }
+                if r.max.x < s.max.x { r.max.x = s.max.x }
+                if r.max.y < s.max.y { r.max.y = s.max.y }
+                r
+            }
+
+            /// Splits the rectangle at the column x, clamped to its bounds,
+            /// into the parts left and right of it.
+            pub fn split_x(self, x: $num) -> (Self, Self) {
+                let x = if x < self.min.x { self.min.x } else if x > self.max.x { self.max.x } else { x };
+                let (mut l, mut r) = (self, self);
+                l.max.x = x;
+                r.min.x = x;
+                (l, r)
+            }
+
+            /// Splits the rectangle at the row y, clamped to its bounds,
+            /// into the parts above and below it.
+            pub fn split_y(self, y: $num) -> (Self, Self) {
+                let y = if y < self.min.y { self.min.y } else if y > self.max.y { self.max.y } else { y };
+                let (mut t, mut b) = (self, self);
+                t.max.y = y;
+                b.min.y = y;
+                (t, b)
+            }
+        }
+
+        impl std::ops::Add<$point> for $rect {
+            type Output = Self;
+            fn add(self, p: $point) -> Self { Self { min: self.min + p, max: self.max + p } }
+        }
+
+        impl std::ops::Sub<$point> for $rect {
+            type Output = Self;
+            fn sub(self, p: $point) -> Self { Self { min: self.min - p, max: self.max - p } }
+        }
+
+        impl AnyRect for $rect {
+            type Num = $num;
+            type Point = $point;
+
+            fn from_points(min: $point, max: $point) -> Self { Self { min, max } }
+            fn min(&self) -> $point { self.min }
+            fn max(&self) -> $point { self.max }
+
+            fn add(self, rhs: $point) -> Self { self + rhs }
+            fn sub(self, rhs: $point) -> Self { self - rhs }
+
+            fn maybe_intersect(self, other: Self) -> Option<Self> { $rect::maybe_intersect(self, other) }
+            fn intersect(self, other: Self) -> Self { $rect::intersect(self, other) }
+            fn union(self, other: Self) -> Self { $rect::union(self, other) }
         }
     }
+}
+
+impl_rect!(Point, Rectangle, isize, 2);
+impl_rect!(PointF, RectangleF, f32, 2.0);
+impl_rect!(Point32, Rectangle32, i32, 2);
+impl_rect!(PointF64, RectangleF64, f64, 2.0);
+
+impl Rectangle {
+    /// Returns the smallest rectangle of pixels that contains r.
+    pub fn from_f32(r: RectangleF) -> Self {
+        Self::new(r.min.x.floor() as isize, r.min.y.floor() as isize,
+                  r.max.x.ceil() as isize, r.max.y.ceil() as isize)
+    }
+}
+
+impl From<Point> for PointF {
+    fn from(p: Point) -> Self { PointF { x: p.x as f32, y: p.y as f32 } }
+}
+
+impl From<Rectangle> for RectangleF {
+    fn from(r: Rectangle) -> Self { RectangleF { min: r.min.into(), max: r.max.into() } }
+}
 
-    pub fn dx(&self) -> isize { self.max.x - self.min.x }
-    pub fn dy(&self) -> isize { self.max.y - self.min.y }
+impl From<Point32> for Point {
+    fn from(p: Point32) -> Self { Point { x: p.x as isize, y: p.y as isize } }
+}
+
+impl From<Rectangle32> for Rectangle {
+    fn from(r: Rectangle32) -> Self { Rectangle { min: r.min.into(), max: r.max.into() } }
+}
+
+impl From<PointF> for PointF64 {
+    fn from(p: PointF) -> Self { PointF64 { x: p.x as f64, y: p.y as f64 } }
+}
+
+impl From<RectangleF> for RectangleF64 {
+    fn from(r: RectangleF) -> Self { RectangleF64 { min: r.min.into(), max: r.max.into() } }
+}
+
+
+/// Flips applied to source pixels by `RGBA::draw_over`.
+///
+/// The diagonal flip (transposition) is applied first, like in Tiled.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Flip {
+    pub h: bool,
+    pub v: bool,
+    pub d: bool,
 }
 
 // In reports whether p is in r.
 pub fn in_rect(p: &Point, r: &Rectangle) -> bool {
-    r.min.x <= p.x && p.x < r.max.x &&
-    r.min.y <= p.y && p.y < r.max.y
+    p.in_rect(r)
 }
 
 // RGBA is an in-memory image whose At method returns color.RGBA values.
+/// The colors are alpha-premultiplied, as in Go's `color.RGBA`: no channel is
+/// larger than the alpha. `draw_over` and the `vg` compositing rely on it.
 pub struct RGBA<'a> {
     /// Pix holds the image's pixels, in R, G, B, A order.
     /// The pixel at (x, y) starts at
@@ -53,6 +350,16 @@ impl<'a> RGBA<'a> {
         }
     }
 
+    /// Wraps an existing R, G, B, A buffer of at least `4*w*h` bytes without clearing it.
+    pub fn from_buf(buf: &'a mut [u8], r: Rectangle) -> Self {
+        let w = r.dx();
+        Self {
+            pix: buf,
+            stride: 4 * w,
+            rect: r,
+        }
+    }
+
     pub fn from_buf32(buf: &'a mut [u32], r: Rectangle) -> Self {
         let (w, h) = (r.dx(), r.dy());
         let pix = unsafe {
@@ -89,48 +396,69 @@ impl<'a> RGBA<'a> {
         (y-self.rect.min.y) * self.stride + (x-self.rect.min.x) * 4
     }
 
-    /*
-    fn (p *RGBA) Set(x, y int, c color.Color) {
-        if !(Point{x, y}.In(p.Rect)) {
+    pub fn set(&mut self, x: isize, y: isize, c: [u8; 4]) {
+        if !in_rect(&Point{x, y}, &self.rect) {
             return
         }
-        i := p.PixOffset(x, y)
-        c1 := color.RGBAModel.Convert(c).(color.RGBA)
-        p.Pix[i+0] = c1.R
-        p.Pix[i+1] = c1.G
-        p.Pix[i+2] = c1.B
-        p.Pix[i+3] = c1.A
+        let i = self.pix_offset(x, y) as usize;
+        self.pix[i..i+4].copy_from_slice(&c);
     }
 
-    fn (p *RGBA) SetRGBA(x, y int, c color.RGBA) {
-        if !(Point{x, y}.In(p.Rect)) {
+    /// Composites the `sr` part of `src` over `self` with its top left corner at `dp`.
+    ///
+    /// `src` is alpha-premultiplied like every `RGBA` and is further scaled by `opacity`.
+    /// Pixels falling outside of `self.rect` are skipped.
+    pub fn draw_over(&mut self, dp: Point, src: &RGBA, sr: Rectangle, flip: Flip, opacity: u8) {
+        let sr = sr.intersect(src.rect);
+        let (w, h) = if flip.d { (sr.dy(), sr.dx()) } else { (sr.dx(), sr.dy()) };
+        let dr = Rectangle { min: dp, max: Point { x: dp.x + w, y: dp.y + h } };
+        let r = dr.intersect(self.rect);
+        if r.is_empty() || opacity == 0 {
             return
         }
-        i := p.PixOffset(x, y)
-        p.Pix[i+0] = c.R
-        p.Pix[i+1] = c.G
-        p.Pix[i+2] = c.B
-        p.Pix[i+3] = c.A
+
+        let opacity = opacity as u32;
+        for y in r.min.y..r.max.y {
+            for x in r.min.x..r.max.x {
+                let (mut u, mut v) = (x - dp.x, y - dp.y);
+                if flip.h { u = w - 1 - u }
+                if flip.v { v = h - 1 - v }
+                if flip.d { std::mem::swap(&mut u, &mut v) }
+
+                let s = src.at(sr.min.x + u, sr.min.y + v);
+                let a = s[3] as u32 * opacity / 0xFF;
+                if a == 0 {
+                    continue
+                }
+                let i = self.pix_offset(x, y) as usize;
+                let d = &mut self.pix[i..i+4];
+                for c in 0..4 {
+                    let s = s[c] as u32 * opacity / 0xFF;
+                    d[c] = (s + d[c] as u32 * (0xFF - a) / 0xFF) as u8;
+                }
+            }
+        }
     }
 
-    // SubImage returns an image representing the portion of the image p visible
-    // through r. The returned value shares pixels with the original image.
-    func (p *RGBA) SubImage(r Rectangle) Image {
-        r = r.Intersect(p.Rect)
-        // If r1 and r2 are Rectangles, r1.Intersect(r2) is not guaranteed to be inside
+    /// Returns an image representing the portion of the image visible through r.
+    /// The returned value shares pixels with the original image.
+    pub fn sub_image(&mut self, r: Rectangle) -> RGBA<'_> {
+        let r = r.intersect(self.rect);
+        // If r1 and r2 are Rectangles, r1.intersect(r2) is not guaranteed to be inside
         // either r1 or r2 if the intersection is empty. Without explicitly checking for
-        // this, the Pix[i:] expression below can panic.
-        if r.Empty() {
-            return &RGBA{}
+        // this, the pix[i..] expression below can panic.
+        if r.is_empty() {
+            return RGBA { pix: &mut [], stride: 0, rect: r }
         }
-        i := p.PixOffset(r.Min.X, r.Min.Y)
-        return &RGBA{
-            Pix:    p.Pix[i:],
-            Stride: p.Stride,
-            Rect:   r,
+        let i = self.pix_offset(r.min.x, r.min.y) as usize;
+        RGBA {
+            pix: &mut self.pix[i..],
+            stride: self.stride,
+            rect: r,
         }
     }
 
+    /*
     // Opaque scans the entire image and reports whether it is fully opaque.
     func (p *RGBA) Opaque() bool {
         if p.Rect.Empty() {
@@ -150,3 +478,117 @@ impl<'a> RGBA<'a> {
     }
     */
 }
+
+/// Draws with `f` over the part `r` of a buffer of 0RGB pixels, like the
+/// canvas buffer, with bounds `rect`.
+///
+/// A 0RGB pixel is B, G, R, X in memory on little-endian targets, so `f`
+/// gets the buffer as an `RGBA` image whose pixels in `r` have been turned
+/// to R, G, B, A order, and they are turned back afterwards. The alpha ends
+/// up in the unused byte. Pixels that `f` changes outside of `r` keep red
+/// and blue swapped.
+pub fn draw_0rgb<F: FnOnce(&mut RGBA)>(buf: &mut [u32], rect: Rectangle, r: Rectangle, f: F) {
+    let mut dst = RGBA::from_buf32(buf, rect);
+    let r = r.intersect(rect);
+    swap_rb(&mut dst, r);
+    f(&mut dst);
+    swap_rb(&mut dst, r);
+}
+
+// swap_rb exchanges the first and third byte of the pixels of img in r.
+fn swap_rb(img: &mut RGBA, r: Rectangle) {
+    for y in r.min.y..r.max.y {
+        let i = img.pix_offset(r.min.x, y) as usize;
+        for p in img.pix[i..i + 4 * r.dx() as usize].chunks_exact_mut(4) {
+            p.swap(0, 2);
+        }
+    }
+}
+
+/// Alpha is an in-memory image of 8-bit coverage or opacity values.
+pub struct Alpha<'a> {
+    /// Pix holds the image's pixels, as alpha values.
+    /// The pixel at (x, y) is Pix[(y-Rect.Min.Y)*Stride + (x-Rect.Min.X)*1].
+    pub pix: &'a mut [u8],
+    /// Stride is the Pix stride (in bytes) between vertically adjacent pixels.
+    pub stride: isize,
+    /// Rect is the image's bounds.
+    pub rect: Rectangle,
+}
+
+impl<'a> Alpha<'a> {
+    /// Returns a new Alpha image with the given bounds, backed by `buf`.
+    pub fn new(buf: &'a mut Vec<u8>, r: Rectangle) -> Self {
+        let (w, h) = (r.dx(), r.dy());
+        buf.clear();
+        buf.resize((w*h) as usize, 0);
+        Self {
+            pix: &mut buf[..],
+            stride: w,
+            rect: r,
+        }
+    }
+
+    /// Wraps an existing buffer of at least `w*h` bytes without clearing it.
+    pub fn from_buf(buf: &'a mut [u8], r: Rectangle) -> Self {
+        Self {
+            pix: buf,
+            stride: r.dx(),
+            rect: r,
+        }
+    }
+
+    pub fn bounds(&self) -> Rectangle { self.rect }
+
+    pub fn at(&self, x: isize, y: isize) -> u8 {
+        if !in_rect(&Point{x, y}, &self.rect) {
+            0
+        } else {
+            self.pix[self.pix_offset(x, y) as usize]
+        }
+    }
+
+    pub fn set(&mut self, x: isize, y: isize, a: u8) {
+        if !in_rect(&Point{x, y}, &self.rect) {
+            return
+        }
+        let i = self.pix_offset(x, y) as usize;
+        self.pix[i] = a;
+    }
+
+    /// Returns the index of the first element of `pix`
+    /// that corresponds to the pixel at (x, y).
+    pub fn pix_offset(&self, x: isize, y: isize) -> isize {
+        (y-self.rect.min.y) * self.stride + (x-self.rect.min.x)
+    }
+
+    /// Returns an image representing the portion of the image visible through r.
+    /// The returned value shares pixels with the original image.
+    pub fn sub_image(&mut self, r: Rectangle) -> Alpha<'_> {
+        let r = r.intersect(self.rect);
+        if r.is_empty() {
+            return Alpha { pix: &mut [], stride: 0, rect: r }
+        }
+        let i = self.pix_offset(r.min.x, r.min.y) as usize;
+        Alpha {
+            pix: &mut self.pix[i..],
+            stride: self.stride,
+            rect: r,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_0rgb_order() {
+        let mut buf = vec![0x00FF_0000; 3];
+        draw_0rgb(&mut buf, Rectangle::from_size(3, 1), Rectangle::new(0, 0, 2, 1), |img| {
+            assert_eq!(img.at(0, 0), [0xFF, 0, 0, 0]);
+            img.set(1, 0, [0, 0, 0xFF, 0xFF]);
+        });
+        assert_eq!(buf, [0x00FF_0000, 0xFF00_00FF, 0x00FF_0000]);
+    }
+}
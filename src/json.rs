@@ -0,0 +1,365 @@
+//! Minimal JSON reader and writer.
+//!
+//! Only what the data formats used by this crate need: atlas manifests,
+//! Tiled maps and Aseprite sheets.
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members are kept in document order.
+    Object(Vec<(String, Value)>),
+}
+
+static NULL: Value = Value::Null;
+
+impl Value {
+    /// Returns the member `key` of an object, or `Null` if there is no such member.
+    pub fn get(&self, key: &str) -> &Value {
+        match self {
+            Value::Object(members) => members.iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self { Value::Bool(v) => Some(v), _ => None }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self { Value::Number(v) => Some(v), _ => None }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_f64().map(|v| v as i64)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self { Value::String(v) => Some(v), _ => None }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self { Value::Array(v) => Some(v), _ => None }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self { Value::Object(v) => Some(v), _ => None }
+    }
+}
+
+impl From<bool> for Value { fn from(v: bool) -> Self { Value::Bool(v) } }
+impl From<f64> for Value { fn from(v: f64) -> Self { Value::Number(v) } }
+impl From<isize> for Value { fn from(v: isize) -> Self { Value::Number(v as f64) } }
+impl From<usize> for Value { fn from(v: usize) -> Self { Value::Number(v as f64) } }
+impl From<&str> for Value { fn from(v: &str) -> Self { Value::String(v.to_string()) } }
+impl From<String> for Value { fn from(v: String) -> Self { Value::String(v) } }
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::Number(v) if v.is_finite() => write!(f, "{}", v),
+            Value::Number(_) => f.write_str("null"),
+            Value::String(v) => write_str(f, v),
+            Value::Array(items) => {
+                f.write_str("[")?;
+                for (i, v) in items.iter().enumerate() {
+                    if i != 0 { f.write_str(",")?; }
+                    write!(f, "{}", v)?;
+                }
+                f.write_str("]")
+            }
+            Value::Object(members) => {
+                f.write_str("{")?;
+                for (i, (k, v)) in members.iter().enumerate() {
+                    if i != 0 { f.write_str(",")?; }
+                    write_str(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+fn write_str(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+/// Error returned by `parse`, with the byte offset where parsing stopped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    pub pos: usize,
+    pub msg: &'static str,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.msg, self.pos)
+    }
+}
+
+impl std::error::Error for Error {}
+
+// MAX_DEPTH is the deepest nesting of arrays and objects that parse accepts,
+// so that malformed input can't overflow the stack.
+const MAX_DEPTH: usize = 512;
+
+/// Parses a JSON document.
+pub fn parse(src: &str) -> Result<Value, Error> {
+    let mut p = Parser { src: src.as_bytes(), pos: 0, depth: 0 };
+    let v = p.value()?;
+    p.skip_ws();
+    if p.pos != p.src.len() {
+        return Err(p.error("trailing characters"));
+    }
+    Ok(v)
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &'static str) -> Error {
+        Error { pos: self.pos, msg }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.pos).cloned()
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), Error> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    fn keyword(&mut self, word: &str, v: Value) -> Result<Value, Error> {
+        if self.src[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(v)
+        } else {
+            Err(self.error("unknown keyword"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        self.skip_ws();
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some(b'n') => self.keyword("null", Value::Null),
+            Some(b't') => self.keyword("true", Value::Bool(true)),
+            Some(b'f') => self.keyword("false", Value::Bool(false)),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'[') | Some(b'{') => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error("nesting too deep"));
+                }
+                self.depth += 1;
+                let v = if self.peek() == Some(b'[') { self.array() } else { self.object() };
+                self.depth -= 1;
+                v
+            }
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, Error> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_ws();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => { self.pos += 1; break }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn object(&mut self) -> Result<Value, Error> {
+        self.pos += 1;
+        let mut members = Vec::new();
+        self.skip_ws();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_ws();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected string key"));
+            }
+            let key = self.string()?;
+            self.skip_ws();
+            self.expect(b':')?;
+            members.push((key, self.value()?));
+            self.skip_ws();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => { self.pos += 1; break }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+        Ok(Value::Object(members))
+    }
+
+    // number reads a number with the grammar of the JSON specification:
+    // an optional minus, an integer part without leading zeros, and an
+    // optional fraction and exponent that have at least one digit each.
+    fn number(&mut self) -> Result<Value, Error> {
+        let start = self.pos;
+        let invalid = Error { pos: start, msg: "invalid number" };
+        if self.peek() == Some(b'-') { self.pos += 1 }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => { self.digits(); }
+            _ => return Err(invalid),
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if !self.digits() { return Err(invalid) }
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() { self.pos += 1 }
+            if !self.digits() { return Err(invalid) }
+        }
+        let text = std::str::from_utf8(&self.src[start..self.pos]).unwrap();
+        text.parse().map(Value::Number).map_err(|_| invalid)
+    }
+
+    // digits skips a run of decimal digits and reports whether there was any.
+    fn digits(&mut self) -> bool {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    fn hex4(&mut self) -> Result<u32, Error> {
+        let digits = self.src.get(self.pos..self.pos + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.expect(b'"')?;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            while let Some(c) = self.peek() {
+                if c == b'"' || c == b'\\' { break }
+                self.pos += 1;
+            }
+            // The input is a &str and we only stop at ASCII bytes,
+            // so the run is always valid UTF-8.
+            out.push_str(std::str::from_utf8(&self.src[start..self.pos]).unwrap());
+
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => { self.pos += 1; return Ok(out) }
+                _ => self.pos += 1,
+            }
+
+            let c = match self.peek() {
+                Some(b'"') => '"',
+                Some(b'\\') => '\\',
+                Some(b'/') => '/',
+                Some(b'b') => '\u{8}',
+                Some(b'f') => '\u{c}',
+                Some(b'n') => '\n',
+                Some(b'r') => '\r',
+                Some(b't') => '\t',
+                Some(b'u') => {
+                    self.pos += 1;
+                    let mut code = self.hex4()?;
+                    if (0xD800..0xDC00).contains(&code) && self.src[self.pos..].starts_with(b"\\u") {
+                        self.pos += 2;
+                        let low = self.hex4()?;
+                        code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                    }
+                    out.push(std::char::from_u32(code).unwrap_or('\u{FFFD}'));
+                    continue;
+                }
+                _ => return Err(self.error("invalid escape")),
+            };
+            self.pos += 1;
+            out.push(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        for &(src, want) in &[("0", 0.0), ("-0", 0.0), ("12", 12.0), ("-1.5", -1.5), ("1e3", 1e3), ("2.5E-2", 2.5e-2), ("1e+2", 100.0)] {
+            assert_eq!(parse(src), Ok(Value::Number(want)), "{}", src);
+        }
+        for &(src, pos) in &[("01", 1), ("1.", 0), (".5", 0), ("-", 0), ("1e", 0), ("1e+", 0), ("+1", 0), ("[1.e2]", 1), ("-01", 2)] {
+            assert_eq!(parse(src).unwrap_err().pos, pos, "{}", src);
+        }
+    }
+
+    #[test]
+    fn nesting() {
+        let ok = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert!(parse(&ok).is_ok());
+        let deep = "[".repeat(MAX_DEPTH + 1) + &"]".repeat(MAX_DEPTH + 1);
+        assert_eq!(parse(&deep), Err(Error { pos: MAX_DEPTH, msg: "nesting too deep" }));
+        let deep = "{\"a\":".repeat(200_000);
+        assert_eq!(parse(&deep).unwrap_err().msg, "nesting too deep");
+    }
+}
@@ -11,10 +11,16 @@ pub mod image;
 pub mod vg;
 pub mod clrs;
 pub mod geom;
+pub mod json;
+pub mod xml;
+pub mod atlas;
+pub mod tilemap;
+pub mod anim;
 
 use minifb::{Window, MouseMode};
 use rusttype::{point, Scale};
 
+use self::geom::Region;
 use self::image::{Rectangle, RGBA};
 
 pub use minifb::{Key, MouseButton, CursorStyle};
@@ -22,10 +28,17 @@ pub use rusttype::Font;
 
 pub type Point = (isize, isize);
 
+/// Window with a pixel buffer to draw into.
+///
+/// The canvas keeps the damage: the areas changed since it was last taken or
+/// presented. Its drawing methods add what they touch. Code that writes to
+/// `buffer_mut`, `image_mut` or the slice behind `DerefMut` has to call
+/// `invalidate` for what it changes.
 pub struct Canvas {
     buffer: Vec<u32>,
     window: Window,
     size: (usize, usize),
+    damage: Region,
 }
 
 impl std::ops::Deref for Canvas {
@@ -42,7 +55,9 @@ impl Canvas {
         let buffer: Vec<u32> = vec![0; width * height];
         let window = Window::new(title, width, height, Default::default())?;
 
-        Ok(Self { buffer, window, size: (width, height) })
+        // Everything is damaged, so that the first present shows the buffer.
+        let damage = Region::from(Rectangle::from_size(width as isize, height as isize));
+        Ok(Self { buffer, window, size: (width, height), damage })
     }
 
     pub fn window(&self) -> &Window { &self.window }
@@ -51,13 +66,46 @@ impl Canvas {
     pub fn buffer_mut(&mut self) -> &mut [u32] { &mut self.buffer }
 
     pub fn image_mut(&mut self) -> RGBA {
-        let (w, h) = self.size;
-        let r = Rectangle::from_size(w as isize, h as isize);
+        let r = self.bounds();
         RGBA::from_buf32(&mut self.buffer, r)
     }
 
+    /// Draws with `f` over the part `r` of the canvas as an `RGBA` image in
+    /// R, G, B, A order, and marks r as changed. The pixels of `image_mut`
+    /// are 0RGB instead, so they show red and blue swapped.
+    pub fn draw_rgba<F: FnOnce(&mut RGBA)>(&mut self, r: Rectangle, f: F) {
+        let bounds = self.bounds();
+        image::draw_0rgb(&mut self.buffer, bounds, r, f);
+        self.invalidate(r);
+    }
+
     pub fn size(&self) -> (usize, usize) { self.size }
 
+    pub fn bounds(&self) -> Rectangle {
+        let (w, h) = self.size;
+        Rectangle::from_size(w as isize, h as isize)
+    }
+
+    /// Returns the areas changed since the damage was last taken or presented.
+    pub fn damage(&self) -> &Region { &self.damage }
+
+    /// Returns the damage and starts a new one, for consumers that send only
+    /// the changed parts of the buffer.
+    pub fn take_damage(&mut self) -> Region {
+        std::mem::take(&mut self.damage)
+    }
+
+    /// Marks r as changed.
+    pub fn invalidate(&mut self, r: Rectangle) {
+        let r = r.intersect(self.bounds());
+        self.damage.union_rect(r);
+    }
+
+    /// Marks the whole canvas as changed.
+    pub fn invalidate_all(&mut self) {
+        self.damage = Region::from(self.bounds());
+    }
+
     pub fn is_open(&self) -> bool { self.window.is_open() }
     pub fn is_keydown(&self, key: Key) -> bool { self.window.is_key_down(key) }
 
@@ -86,21 +134,38 @@ impl Canvas {
         self.window.update()
     }
 
+    /// Presents the whole buffer and clears the damage.
     pub fn redraw(&mut self) -> minifb::Result<()> {
+        self.damage.clear();
         self.window.update_with_buffer(&self.buffer)
     }
 
+    /// Presents the buffer if anything changed since it was last presented,
+    /// and only processes window events otherwise.
+    ///
+    /// Returns whether the buffer was presented.
+    pub fn present(&mut self) -> minifb::Result<bool> {
+        if self.damage.is_empty() {
+            self.window.update();
+            return Ok(false)
+        }
+        self.redraw()?;
+        Ok(true)
+    }
+
     pub fn clear(&mut self) {
         self.fill(0);
     }
 
     pub fn fill(&mut self, color: u32) {
         self.buffer.iter_mut().for_each(|i| *i = color);
+        self.invalidate_all();
     }
 
     pub fn pixel_mut(&mut self, x: usize, y: usize) -> &mut u32 {
         let (w, h) = self.size();
         assert!(x < w && y < h, "{}x{}", x, y);
+        self.invalidate(Rectangle::new(x as isize, y as isize, x as isize + 1, y as isize + 1));
         let idx = x + y * w;
         unsafe { self.buffer.get_unchecked_mut(idx) }
     }
@@ -110,10 +175,13 @@ impl Canvas {
         if x < w && y < h {
             let idx = x + y * w;
             unsafe { *self.buffer.get_unchecked_mut(idx) = color; }
+            self.invalidate(Rectangle::new(x as isize, y as isize, x as isize + 1, y as isize + 1));
         }
     }
 
     pub fn line(&mut self, start: Point, end: Point, color: u32) {
+        // Antialiasing reaches the pixels next to the line.
+        self.invalidate(Rectangle::new(start.0, start.1, end.0, end.1).inset(-2));
         let (w, h) = self.size();
         let (w, h) = (w as isize, h as isize);
         wu::clipped_aaline(start, end, (w, h), |x, y, v| {
@@ -131,6 +199,7 @@ impl Canvas {
         if y < 0 || y >= h { return }
         let x1 = x1.max(0);
         let x2 = x2.min(w);
+        self.invalidate(Rectangle::new(x1, y, x2.max(x1), y + 1));
 
         for x in x1..x2 {
             let idx = (x + y * w) as usize;
@@ -145,6 +214,7 @@ impl Canvas {
         if x < 0 || x >= w { return }
         let y1 = y1.max(0);
         let y2 = y2.min(h);
+        self.invalidate(Rectangle::new(x, y1, x + 1, y2.max(y1)));
 
         for y in y1..y2 {
             let idx = (x + y * w) as usize;
@@ -161,6 +231,10 @@ impl Canvas {
             let point = point(pos.0, pos.1 + v_metrics.ascent * (line + 1) as f32);
             for glyph in font.layout(text, scale, point) {
                 if let Some(bbox) = glyph.pixel_bounding_box() {
+                    self.invalidate(Rectangle::new(
+                        bbox.min.x as isize, bbox.min.y as isize,
+                        bbox.max.x as isize, bbox.max.y as isize,
+                    ));
                     glyph.draw(|x, y, v| {
                         let x = (x + bbox.min.x as u32) as usize;
                         let y = (y + bbox.min.y as u32) as usize;
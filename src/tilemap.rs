@@ -0,0 +1,420 @@
+//! Grid tile maps with support for loading Tiled JSON (`.tmj`) maps.
+
+use std::fmt;
+
+use crate::Canvas;
+use crate::image::{Flip, Point, Rectangle, RGBA};
+use crate::json::{self, Value};
+
+const FLIPPED_H: u32 = 0x8000_0000;
+const FLIPPED_V: u32 = 0x4000_0000;
+const FLIPPED_D: u32 = 0x2000_0000;
+const GID_MASK: u32 = 0x0FFF_FFFF;
+
+/// A tile reference as stored in Tiled maps:
+/// a global tile id in the low 28 bits and the flip flags in the high ones.
+///
+/// Zero is an empty cell.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Tile(pub u32);
+
+impl Tile {
+    pub fn new(gid: u32, flip: Flip) -> Self {
+        let mut t = gid & GID_MASK;
+        if flip.h { t |= FLIPPED_H }
+        if flip.v { t |= FLIPPED_V }
+        if flip.d { t |= FLIPPED_D }
+        Tile(t)
+    }
+
+    pub fn gid(self) -> u32 { self.0 & GID_MASK }
+
+    pub fn flip(self) -> Flip {
+        Flip {
+            h: self.0 & FLIPPED_H != 0,
+            v: self.0 & FLIPPED_V != 0,
+            d: self.0 & FLIPPED_D != 0,
+        }
+    }
+}
+
+/// Tiles cut from a single image.
+#[derive(Clone, Debug, Default)]
+pub struct Tileset {
+    pub name: String,
+    /// Global id of the first tile in this set.
+    pub first_gid: u32,
+    pub tile_width: isize,
+    pub tile_height: isize,
+    pub columns: isize,
+    pub tile_count: u32,
+    /// Pixels around the tiles at the image border.
+    pub margin: isize,
+    /// Pixels between neighbour tiles.
+    pub spacing: isize,
+    /// Image path as written in the map, for the caller to load.
+    pub image: String,
+    /// Path of an external tileset (`.tsj`) which wasn't loaded.
+    pub source: Option<String>,
+}
+
+impl Tileset {
+    /// Returns the bounds of the local tile `id` inside the tileset image.
+    pub fn tile_rect(&self, id: u32) -> Rectangle {
+        let columns = self.columns.max(1);
+        let (col, row) = (id as isize % columns, id as isize / columns);
+        let x = self.margin + col * (self.tile_width + self.spacing);
+        let y = self.margin + row * (self.tile_height + self.spacing);
+        Rectangle {
+            min: Point { x, y },
+            max: Point { x: x + self.tile_width, y: y + self.tile_height },
+        }
+    }
+}
+
+/// A rectangular grid of tiles.
+#[derive(Clone, Debug)]
+pub struct Layer {
+    pub name: String,
+    /// Grid position of the first tile, non-zero for infinite maps.
+    pub origin: (isize, isize),
+    pub width: usize,
+    pub height: usize,
+    /// Row-major, `width * height` tiles.
+    pub tiles: Vec<Tile>,
+    pub visible: bool,
+    pub opacity: f32,
+    /// Drawing offset in pixels.
+    pub offset: (f32, f32),
+    /// Camera scroll multiplier: 1.0 moves with the camera, 0.0 is fixed on screen.
+    pub parallax: (f32, f32),
+}
+
+impl Layer {
+    pub fn new(name: &str, width: usize, height: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            origin: (0, 0),
+            width,
+            height,
+            tiles: vec![Tile(0); width * height],
+            visible: true,
+            opacity: 1.0,
+            offset: (0.0, 0.0),
+            parallax: (1.0, 1.0),
+        }
+    }
+
+    /// Returns the tile at the grid position, or an empty tile outside of the layer.
+    pub fn get(&self, x: isize, y: isize) -> Tile {
+        let (x, y) = (x - self.origin.0, y - self.origin.1);
+        if x < 0 || y < 0 || x >= self.width as isize || y >= self.height as isize {
+            Tile(0)
+        } else {
+            self.tiles[y as usize * self.width + x as usize]
+        }
+    }
+
+    pub fn set(&mut self, x: isize, y: isize, tile: Tile) {
+        let (x, y) = (x - self.origin.0, y - self.origin.1);
+        if x >= 0 && y >= 0 && x < self.width as isize && y < self.height as isize {
+            self.tiles[y as usize * self.width + x as usize] = tile;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Tilemap {
+    pub tile_width: isize,
+    pub tile_height: isize,
+    pub tilesets: Vec<Tileset>,
+    /// Drawn in order, the first one at the bottom.
+    pub layers: Vec<Layer>,
+}
+
+impl Tilemap {
+    pub fn new(tile_width: isize, tile_height: isize) -> Self {
+        Self {
+            tile_width,
+            tile_height,
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+        }
+    }
+
+    /// Returns the tileset index and the local tile id of a global id.
+    pub fn find_tileset(&self, gid: u32) -> Option<(usize, u32)> {
+        self.tilesets.iter().enumerate()
+            .filter(|(_, ts)| ts.first_gid <= gid)
+            .max_by_key(|(_, ts)| ts.first_gid)
+            .map(|(i, ts)| (i, gid - ts.first_gid))
+            .filter(|&(i, id)| self.tilesets[i].tile_count == 0 || id < self.tilesets[i].tile_count)
+    }
+
+    /// Draws all visible layers into the `view` part of `dst`.
+    ///
+    /// `camera` is the map position, in pixels, shown at the top left corner of `view`.
+    /// `images[i]` holds the pixels of `self.tilesets[i]`.
+    /// Only the tiles intersecting `view` are drawn.
+    pub fn draw(&self, dst: &mut RGBA, view: Rectangle, camera: (f32, f32), images: &[RGBA]) {
+        let clip = view.intersect(dst.rect);
+        if clip.is_empty() {
+            return
+        }
+        let (tw, th) = (self.tile_width, self.tile_height);
+        if tw <= 0 || th <= 0 {
+            return
+        }
+
+        for layer in self.layers.iter().filter(|l| l.visible && l.opacity > 0.0) {
+            let opacity = (layer.opacity.min(1.0) * 255.0).round() as u8;
+
+            // Screen position of the grid origin.
+            let ox = view.min.x + (layer.offset.0 - camera.0 * layer.parallax.0).round() as isize;
+            let oy = view.min.y + (layer.offset.1 - camera.1 * layer.parallax.1).round() as isize;
+
+            // Tiles larger than the grid are aligned to the bottom left of their cell,
+            // so look a bit further for ones reaching into the view.
+            let extra_w = self.tilesets.iter().map(|ts| ts.tile_width - tw).max().unwrap_or(0).max(0);
+            let extra = self.tilesets.iter().map(|ts| ts.tile_height - th).max().unwrap_or(0).max(0);
+
+            let x0 = div_floor(clip.min.x - ox - extra_w, tw).max(layer.origin.0);
+            let y0 = div_floor(clip.min.y - oy, th).max(layer.origin.1);
+            let x1 = (div_floor(clip.max.x - ox - 1, tw) + 1)
+                .min(layer.origin.0 + layer.width as isize);
+            let y1 = (div_floor(clip.max.y - oy - 1 + extra, th) + 1)
+                .min(layer.origin.1 + layer.height as isize);
+
+            let mut dst = dst.sub_image(clip);
+
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let tile = layer.get(x, y);
+                    if tile.gid() == 0 {
+                        continue
+                    }
+                    let (i, id) = match self.find_tileset(tile.gid()) {
+                        Some(found) => found,
+                        None => continue,
+                    };
+                    let image = match images.get(i) {
+                        Some(image) => image,
+                        None => continue,
+                    };
+                    let ts = &self.tilesets[i];
+                    let dp = Point {
+                        x: ox + x * tw,
+                        y: oy + (y + 1) * th - ts.tile_height,
+                    };
+                    dst.draw_over(dp, image, ts.tile_rect(id), tile.flip(), opacity);
+                }
+            }
+        }
+    }
+
+    /// Draws the map over the whole canvas.
+    pub fn render(&self, canvas: &mut Canvas, camera: (f32, f32), images: &[RGBA]) {
+        let view = canvas.bounds();
+        canvas.draw_rgba(view, |dst| self.draw(dst, view, camera, images));
+    }
+
+    /// Loads an orthogonal map saved by Tiled in the JSON format.
+    ///
+    /// Tile layers, group layers (flattened into their children),
+    /// embedded tilesets, CSV and uncompressed base64 data and infinite maps are supported.
+    /// External tilesets are listed with their `source` set and have to be filled by the caller.
+    /// Object and image layers are skipped.
+    pub fn from_tiled_json(src: &str) -> Result<Self, Error> {
+        let doc = json::parse(src)?;
+
+        match doc.get("orientation").as_str() {
+            None | Some("orthogonal") => (),
+            Some(_) => return Err(Error::Format("only orthogonal maps are supported")),
+        }
+
+        let mut map = Self::new(
+            num(&doc, "tilewidth").ok_or(Error::Format("missing tilewidth"))? as isize,
+            num(&doc, "tileheight").ok_or(Error::Format("missing tileheight"))? as isize,
+        );
+
+        for ts in doc.get("tilesets").as_array().unwrap_or(&[]) {
+            let first_gid = num(ts, "firstgid").ok_or(Error::Format("missing tileset firstgid"))? as u32;
+            let tile_width = num(ts, "tilewidth").unwrap_or(map.tile_width as f64) as isize;
+            let columns = match num(ts, "columns") {
+                Some(c) => c as isize,
+                None => {
+                    let margin = num(ts, "margin").unwrap_or(0.0) as isize;
+                    let spacing = num(ts, "spacing").unwrap_or(0.0) as isize;
+                    let w = num(ts, "imagewidth").unwrap_or(0.0) as isize;
+                    (w - 2 * margin + spacing) / (tile_width + spacing).max(1)
+                }
+            };
+            map.tilesets.push(Tileset {
+                name: ts.get("name").as_str().unwrap_or("").to_string(),
+                first_gid,
+                tile_width,
+                tile_height: num(ts, "tileheight").unwrap_or(map.tile_height as f64) as isize,
+                columns,
+                tile_count: num(ts, "tilecount").unwrap_or(0.0) as u32,
+                margin: num(ts, "margin").unwrap_or(0.0) as isize,
+                spacing: num(ts, "spacing").unwrap_or(0.0) as isize,
+                image: ts.get("image").as_str().unwrap_or("").to_string(),
+                source: ts.get("source").as_str().map(str::to_string),
+            });
+        }
+        map.tilesets.sort_by_key(|ts| ts.first_gid);
+
+        let root = Group { opacity: 1.0, visible: true, offset: (0.0, 0.0), parallax: (1.0, 1.0) };
+        load_layers(&mut map.layers, doc.get("layers"), &root)?;
+
+        Ok(map)
+    }
+}
+
+fn div_floor(a: isize, b: isize) -> isize {
+    let d = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) { d - 1 } else { d }
+}
+
+fn num(v: &Value, key: &str) -> Option<f64> {
+    v.get(key).as_f64()
+}
+
+/// Properties inherited from group layers.
+struct Group {
+    opacity: f32,
+    visible: bool,
+    offset: (f32, f32),
+    parallax: (f32, f32),
+}
+
+fn load_layers(out: &mut Vec<Layer>, layers: &Value, parent: &Group) -> Result<(), Error> {
+    for l in layers.as_array().unwrap_or(&[]) {
+        let group = Group {
+            opacity: parent.opacity * num(l, "opacity").unwrap_or(1.0) as f32,
+            visible: parent.visible && l.get("visible").as_bool().unwrap_or(true),
+            offset: (
+                parent.offset.0 + num(l, "offsetx").unwrap_or(0.0) as f32,
+                parent.offset.1 + num(l, "offsety").unwrap_or(0.0) as f32,
+            ),
+            parallax: (
+                parent.parallax.0 * num(l, "parallaxx").unwrap_or(1.0) as f32,
+                parent.parallax.1 * num(l, "parallaxy").unwrap_or(1.0) as f32,
+            ),
+        };
+
+        match l.get("type").as_str() {
+            Some("group") => load_layers(out, l.get("layers"), &group)?,
+            Some("tilelayer") => {
+                let name = l.get("name").as_str().unwrap_or("");
+                let compression = l.get("compression").as_str().unwrap_or("");
+                if !compression.is_empty() {
+                    return Err(Error::Format("compressed layer data is not supported"));
+                }
+                let base64 = l.get("encoding").as_str() == Some("base64");
+
+                let mut layer = match l.get("chunks").as_array() {
+                    Some(chunks) => {
+                        let x = num(l, "startx").unwrap_or(0.0) as isize;
+                        let y = num(l, "starty").unwrap_or(0.0) as isize;
+                        let w = num(l, "width").unwrap_or(0.0) as usize;
+                        let h = num(l, "height").unwrap_or(0.0) as usize;
+                        let mut layer = Layer::new(name, w, h);
+                        layer.origin = (x, y);
+                        for c in chunks {
+                            let cx = num(c, "x").unwrap_or(0.0) as isize;
+                            let cy = num(c, "y").unwrap_or(0.0) as isize;
+                            let cw = num(c, "width").unwrap_or(0.0) as isize;
+                            let data = layer_data(c.get("data"), base64)?;
+                            for (i, t) in data.into_iter().enumerate() {
+                                let i = i as isize;
+                                layer.set(cx + i % cw.max(1), cy + i / cw.max(1), t);
+                            }
+                        }
+                        layer
+                    }
+                    None => {
+                        let w = num(l, "width").unwrap_or(0.0) as usize;
+                        let h = num(l, "height").unwrap_or(0.0) as usize;
+                        let mut layer = Layer::new(name, w, h);
+                        let data = layer_data(l.get("data"), base64)?;
+                        if data.len() != w * h {
+                            return Err(Error::Format("layer data doesn't match its size"));
+                        }
+                        layer.tiles = data;
+                        layer
+                    }
+                };
+
+                layer.visible = group.visible;
+                layer.opacity = group.opacity;
+                layer.offset = group.offset;
+                layer.parallax = group.parallax;
+                out.push(layer);
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn layer_data(data: &Value, base64: bool) -> Result<Vec<Tile>, Error> {
+    if base64 {
+        let text = data.as_str().ok_or(Error::Format("expected base64 layer data"))?;
+        let bytes = decode_base64(text).ok_or(Error::Format("invalid base64 layer data"))?;
+        Ok(bytes.chunks(4)
+            .map(|b| Tile(u32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+            .collect())
+    } else {
+        data.as_array()
+            .ok_or(Error::Format("expected layer data array"))?
+            .iter()
+            .map(|v| v.as_f64().map(|v| Tile(v as u32)).ok_or(Error::Format("invalid tile")))
+            .collect()
+    }
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in text.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' | b' ' | b'\n' | b'\r' | b'\t' => continue,
+            _ => return None,
+        };
+        acc = acc << 6 | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    if out.len() % 4 != 0 { None } else { Some(out) }
+}
+
+/// Error returned by `Tilemap::from_tiled_json`.
+#[derive(Debug)]
+pub enum Error {
+    Json(json::Error),
+    Format(&'static str),
+}
+
+impl From<json::Error> for Error {
+    fn from(err: json::Error) -> Self { Error::Json(err) }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Json(err) => write!(f, "invalid JSON: {}", err),
+            Error::Format(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,238 @@
+// This file contains circular and elliptical arcs, for both the Rasterizer
+// and Path, converted to cubic Bézier curves before they are flattened.
+
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use super::Rasterizer;
+use super::flatten::{DEFAULT_TOLERANCE, MIN_TOLERANCE};
+use super::path::{Path, Segment};
+
+// QUARTER_ERROR is the largest distance between a quarter of the unit circle
+// and its cubic approximation. It grows with the sixth power of the angle.
+const QUARTER_ERROR: f32 = 2.7e-4;
+
+// ellipse returns the arc of the ellipse centered at c with radii r, rotated
+// by the angle with the given sine and cosine, from the angle theta to
+// theta + delta, ending exactly at end. The curves are at most tolerance
+// away from the arc.
+//
+// Each piece spans at most a quarter turn and is approximated by a cubic
+// Bézier curve whose control points are on the tangents, at 4/3*tan(a/4) of
+// the radius. Large arcs are cut into more pieces to stay within the tolerance.
+pub(crate) fn ellipse(c: [f32; 2], r: [f32; 2], rot: [f32; 2], theta: f32, delta: f32, end: [f32; 2], tolerance: f32) -> Vec<Segment> {
+    let [sin, cos] = rot;
+    let at = |t: f32| {
+        let (ex, ey) = (r[0] * t.cos(), r[1] * t.sin());
+        [c[0] + cos * ex - sin * ey, c[1] + sin * ex + cos * ey]
+    };
+    let tangent = |t: f32| {
+        let (ex, ey) = (-r[0] * t.sin(), r[1] * t.cos());
+        [cos * ex - sin * ey, sin * ex + cos * ey]
+    };
+
+    let quarters = delta.abs() / FRAC_PI_2;
+    let radius = r[0].abs().max(r[1].abs());
+    let k = (QUARTER_ERROR * radius / tolerance.max(MIN_TOLERANCE)).powf(1.0 / 6.0).max(1.0);
+    let n = (quarters * k).ceil().clamp(1.0, 1024.0) as usize;
+
+    let step = delta / n as f32;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    let mut segs = Vec::with_capacity(n);
+    let mut t0 = theta;
+    let mut p0 = at(t0);
+    for i in 0..n {
+        let t1 = t0 + step;
+        let p1 = if i + 1 == n { end } else { at(t1) };
+        let (d0, d1) = (tangent(t0), tangent(t1));
+        segs.push(Segment::CubeTo(
+            [p0[0] + k * d0[0], p0[1] + k * d0[1]],
+            [p1[0] - k * d1[0], p1[1] - k * d1[1]],
+            p1,
+        ));
+        t0 = t1;
+        p0 = p1;
+    }
+    segs
+}
+
+// svg returns an SVG elliptical arc from p0 to p1. Radii that are too small
+// to reach p1 are scaled up, as SVG requires, and a zero radius gives a
+// straight line.
+pub(crate) fn svg(p0: [f32; 2], r: [f32; 2], rotation: f32, large: bool, sweep: bool, p1: [f32; 2], tolerance: f32) -> Vec<Segment> {
+    // This follows the endpoint to center conversion of the SVG
+    // specification, appendix F.6.5, with the radii corrections of F.6.6.
+    let ([x1, y1], [x, y]) = (p0, p1);
+    if x1 == x && y1 == y {
+        return Vec::new()
+    }
+    let (mut rx, mut ry) = (r[0].abs(), r[1].abs());
+    if rx == 0.0 || ry == 0.0 {
+        return vec![Segment::LineTo(p1)]
+    }
+
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let (dx, dy) = ((x1 - x) / 2.0, (y1 - y) / 2.0);
+    let x1p = cos * dx + sin * dy;
+    let y1p = -sin * dx + cos * dy;
+
+    let lambda = (x1p * x1p) / (rx * rx) + (y1p * y1p) / (ry * ry);
+    if lambda > 1.0 {
+        let k = lambda.sqrt();
+        rx *= k;
+        ry *= k;
+    }
+
+    let num = rx * rx * ry * ry - rx * rx * y1p * y1p - ry * ry * x1p * x1p;
+    let den = rx * rx * y1p * y1p + ry * ry * x1p * x1p;
+    let mut k = (num / den).max(0.0).sqrt();
+    if large == sweep {
+        k = -k;
+    }
+    let cxp = k * rx * y1p / ry;
+    let cyp = -k * ry * x1p / rx;
+    let cx = cos * cxp - sin * cyp + (x1 + x) / 2.0;
+    let cy = sin * cxp + cos * cyp + (y1 + y) / 2.0;
+
+    let angle = |ux: f32, uy: f32| uy.atan2(ux);
+    let theta = angle((x1p - cxp) / rx, (y1p - cyp) / ry);
+    let mut delta = angle((-x1p - cxp) / rx, (-y1p - cyp) / ry) - theta;
+    if sweep && delta < 0.0 {
+        delta += 2.0 * PI;
+    } else if !sweep && delta > 0.0 {
+        delta -= 2.0 * PI;
+    }
+
+    ellipse([cx, cy], [rx, ry], [sin, cos], theta, delta, p1, tolerance)
+}
+
+// circle returns the start point and the curves of the arc of the circle
+// centered at c with radius r, from the angle start to end, as the HTML
+// canvas arc method draws it.
+pub(crate) fn circle(c: [f32; 2], r: f32, start: f32, end: f32, anticlockwise: bool, tolerance: f32) -> ([f32; 2], Vec<Segment>) {
+    let r = r.abs();
+    let delta = if !anticlockwise {
+        if end - start >= 2.0 * PI { 2.0 * PI } else { (end - start).rem_euclid(2.0 * PI) }
+    } else if start - end >= 2.0 * PI {
+        -2.0 * PI
+    } else {
+        -(start - end).rem_euclid(2.0 * PI)
+    };
+    let at = |t: f32| [c[0] + r * t.cos(), c[1] + r * t.sin()];
+    let p0 = at(start);
+    if delta == 0.0 || r == 0.0 {
+        return (p0, Vec::new())
+    }
+    (p0, ellipse(c, [r, r], [0.0, 1.0], start, delta, at(start + delta), tolerance))
+}
+
+// tangent returns the arc of radius r that is tangent to the line from p0 to
+// p1 and to the line from p1 to p2, joined to p0 by a straight line, as the
+// HTML canvas arcTo method draws it.
+pub(crate) fn tangent(p0: [f32; 2], p1: [f32; 2], p2: [f32; 2], r: f32, tolerance: f32) -> Vec<Segment> {
+    let (ux, uy) = (p0[0] - p1[0], p0[1] - p1[1]);
+    let (vx, vy) = (p2[0] - p1[0], p2[1] - p1[1]);
+    let (lu, lv) = ((ux * ux + uy * uy).sqrt(), (vx * vx + vy * vy).sqrt());
+    let cross = ux * vy - uy * vx;
+    if r == 0.0 || lu == 0.0 || lv == 0.0 || cross.abs() <= 1e-6 * lu * lv {
+        return vec![Segment::LineTo(p1)]
+    }
+
+    // The arc touches both lines at the distance r / tan(a/2) from p1,
+    // where a is the angle between them.
+    let cos = ((ux * vx + uy * vy) / (lu * lv)).clamp(-1.0, 1.0);
+    let half = cos.acos() / 2.0;
+    let d = r.abs() / half.tan();
+    let t0 = [p1[0] + ux / lu * d, p1[1] + uy / lu * d];
+    let t1 = [p1[0] + vx / lv * d, p1[1] + vy / lv * d];
+
+    // The cross product is negative when the path turns clockwise on
+    // screen, toward growing angles, which is a positive sweep.
+    let mut segs = vec![Segment::LineTo(t0)];
+    segs.extend(svg(t0, [r, r], 0.0, false, cross < 0.0, t1, tolerance));
+    segs
+}
+
+// A Path is flattened later, with a tolerance it does not know yet, so its
+// arcs are approximated within the Rasterizer's default tolerance.
+impl Path {
+    /// Adds an SVG elliptical arc from the pen to (x, y).
+    ///
+    /// `rotation` is the angle of the x axis of the ellipse, in degrees.
+    /// Radii that are too small to reach (x, y) are scaled up, as SVG
+    /// requires, and a zero radius gives a straight line.
+    #[allow(clippy::too_many_arguments)]
+    pub fn svg_arc_to(&mut self, rx: f32, ry: f32, rotation: f32, large: bool, sweep: bool, x: f32, y: f32) -> &mut Self {
+        let segs = svg(self.pen(), [rx, ry], rotation, large, sweep, [x, y], DEFAULT_TOLERANCE);
+        self.extend(segs);
+        self
+    }
+
+    /// Adds the arc of the circle centered at (cx, cy) from the angle `start`
+    /// to `end`, in radians, like the HTML canvas `arc` method.
+    ///
+    /// Angles grow clockwise on screen. The arc is joined to the current
+    /// subpath with a line, or starts a new one after `close_path` or on an
+    /// empty path.
+    pub fn arc(&mut self, cx: f32, cy: f32, r: f32, start: f32, end: f32, anticlockwise: bool) -> &mut Self {
+        let (p0, segs) = circle([cx, cy], r, start, end, anticlockwise, DEFAULT_TOLERANCE);
+        match self.iter().last() {
+            None | Some(Segment::Close) => self.move_to(p0[0], p0[1]),
+            _ => self.line_to(p0[0], p0[1]),
+        };
+        self.extend(segs);
+        self
+    }
+
+    /// Adds a line from the pen toward (x1, y1) and an arc of radius `r`
+    /// tangent to it and to the line from (x1, y1) to (x2, y2), like the
+    /// HTML canvas `arcTo` method.
+    pub fn arc_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, r: f32) -> &mut Self {
+        let segs = tangent(self.pen(), [x1, y1], [x2, y2], r, DEFAULT_TOLERANCE);
+        self.extend(segs);
+        self
+    }
+}
+
+// The Rasterizer approximates arcs within its own tolerance.
+impl Rasterizer {
+    /// Adds an SVG elliptical arc from the pen to (x, y), and moves the pen to (x, y).
+    ///
+    /// `rotation` is the angle of the x axis of the ellipse, in degrees.
+    /// Radii that are too small to reach (x, y) are scaled up, as SVG
+    /// requires, and a zero radius gives a straight line.
+    #[allow(clippy::too_many_arguments)]
+    pub fn svg_arc_to(&mut self, rx: f32, ry: f32, rotation: f32, large: bool, sweep: bool, x: f32, y: f32) {
+        let segs = svg(self.pen(), [rx, ry], rotation, large, sweep, [x, y], self.tolerance);
+        self.add_curves(&segs);
+    }
+
+    /// Adds the arc of the circle centered at (cx, cy) from the angle `start`
+    /// to `end`, in radians, like the HTML canvas `arc` method.
+    ///
+    /// Angles grow clockwise on screen. The arc is joined to the pen with a
+    /// line, so call `move_to` with the start point first to begin a new path
+    /// with it.
+    pub fn arc(&mut self, cx: f32, cy: f32, r: f32, start: f32, end: f32, anticlockwise: bool) {
+        let (p0, segs) = circle([cx, cy], r, start, end, anticlockwise, self.tolerance);
+        self.line_to(p0[0], p0[1]);
+        self.add_curves(&segs);
+    }
+
+    /// Adds a line from the pen toward (x1, y1) and an arc of radius `r`
+    /// tangent to it and to the line from (x1, y1) to (x2, y2), like the
+    /// HTML canvas `arcTo` method.
+    pub fn arc_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, r: f32) {
+        let segs = tangent(self.pen(), [x1, y1], [x2, y2], r, self.tolerance);
+        self.add_curves(&segs);
+    }
+
+    fn add_curves(&mut self, segs: &[Segment]) {
+        for seg in segs {
+            match *seg {
+                Segment::LineTo([x, y]) => self.line_to(x, y),
+                Segment::CubeTo([bx, by], [cx, cy], [dx, dy]) => self.cube_to(bx, by, cx, cy, dx, dy),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
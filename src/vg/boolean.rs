@@ -0,0 +1,372 @@
+// This file contains boolean operations on the areas that paths fill.
+//
+// Both paths are flattened and every line is split where it crosses another
+// one, so that no two pieces cross. A piece is then on the boundary of the
+// result when the result holds the area on one of its sides but not on the
+// other, which is found by testing both paths at two points close to the
+// middle of the piece. Kept pieces are turned to have the result on the same
+// side and linked end to end into contours.
+//
+// This is quadratic in the number of lines, which is fine for the shapes of
+// a user interface, and handles self-intersecting and touching contours.
+
+use std::collections::{HashMap, HashSet};
+
+use super::FillRule;
+use super::flatten::polylines;
+use super::path::Path;
+
+/// A boolean operation on the areas of two paths.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BoolOp {
+    /// The area of either path.
+    Union,
+    /// The area of both paths.
+    Intersection,
+    /// The area of the first path but not of the second.
+    Difference,
+    /// The area of exactly one of the paths.
+    Xor,
+}
+
+impl BoolOp {
+    fn apply(self, a: bool, b: bool) -> bool {
+        match self {
+            BoolOp::Union => a || b,
+            BoolOp::Intersection => a && b,
+            BoolOp::Difference => a && !b,
+            BoolOp::Xor => a != b,
+        }
+    }
+}
+
+type Point = [f32; 2];
+
+struct Edge {
+    a: Point,
+    b: Point,
+    // from_b tells whether the edge comes from the second path.
+    from_b: bool,
+    // splits are the points where the edge is to be cut.
+    splits: Vec<Point>,
+}
+
+impl Path {
+    /// Returns the outline of the area that `op` gives from the areas of
+    /// this path and of `other`, both filled with `rule`.
+    ///
+    /// Curves are flattened within `tolerance`, so the result only has
+    /// lines. Its contours are closed and wind the same way around the area,
+    /// so it can be filled with either rule.
+    pub fn boolean(&self, other: &Path, op: BoolOp, rule: FillRule, tolerance: f32) -> Path {
+        let mut edges = Vec::new();
+        for (path, from_b) in [(self, false), (other, true)].iter() {
+            for (pts, _) in polylines(path, tolerance) {
+                for i in 0..pts.len() {
+                    let (a, b) = (pts[i], pts[(i + 1) % pts.len()]);
+                    if a != b {
+                        edges.push(Edge { a, b, from_b: *from_b, splits: Vec::new() });
+                    }
+                }
+            }
+        }
+
+        split(&mut edges);
+
+        // Cut the edges into pieces, and keep one of the pieces that lie
+        // over each other.
+        let mut pieces: Vec<(Point, Point)> = Vec::new();
+        let mut seen = HashSet::new();
+        for e in &mut edges {
+            let (a, d) = ([e.a[0] as f64, e.a[1] as f64], [(e.b[0] - e.a[0]) as f64, (e.b[1] - e.a[1]) as f64]);
+            let at = |p: &Point| (p[0] as f64 - a[0]) * d[0] + (p[1] as f64 - a[1]) * d[1];
+            e.splits.sort_by(|p, q| at(p).partial_cmp(&at(q)).unwrap());
+            let mut prev = e.a;
+            for &p in e.splits.iter().chain(Some(&e.b)) {
+                if p == prev {
+                    continue
+                }
+                let key = if key(prev) < key(p) { (key(prev), key(p)) } else { (key(p), key(prev)) };
+                if seen.insert(key) {
+                    pieces.push((prev, p));
+                }
+                prev = p;
+            }
+        }
+
+        let (ea, eb): (Vec<&Edge>, Vec<&Edge>) = edges.iter().partition(|e| !e.from_b);
+        let inside = |edges: &[&Edge], p: [f64; 2]| {
+            let w: i32 = edges.iter().map(|e| crossing(e.a, e.b, p)).sum();
+            match rule {
+                FillRule::NonZero => w != 0,
+                FillRule::EvenOdd => w % 2 != 0,
+            }
+        };
+
+        // The boundary pieces, turned so that the result is on their left.
+        let mut out: HashMap<[u32; 2], Vec<Point>> = HashMap::new();
+        let mut count = 0;
+        for &(p, q) in &pieces {
+            let (dx, dy) = ((q[0] - p[0]) as f64, (q[1] - p[1]) as f64);
+            let len = (dx * dx + dy * dy).sqrt();
+            let eps = (len * 1e-3).min(1e-3) / len;
+            let m = [(p[0] as f64 + q[0] as f64) / 2.0, (p[1] as f64 + q[1] as f64) / 2.0];
+            let left = [m[0] + dy * eps, m[1] - dx * eps];
+            let right = [m[0] - dy * eps, m[1] + dx * eps];
+            let l = op.apply(inside(&ea, left), inside(&eb, left));
+            let r = op.apply(inside(&ea, right), inside(&eb, right));
+            if l == r {
+                continue
+            }
+            let (from, to) = if l { (p, q) } else { (q, p) };
+            out.entry(key(from)).or_default().push(to);
+            count += 1;
+        }
+
+        link(out, count)
+    }
+}
+
+// split adds to every edge the points where other edges cross or touch it.
+// A point is computed once for both edges, so that their pieces share ends.
+fn split(edges: &mut [Edge]) {
+    for i in 0..edges.len() {
+        for j in i + 1..edges.len() {
+            let (p0, p1, q0, q1) = (edges[i].a, edges[i].b, edges[j].a, edges[j].b);
+            if p0[0].max(p1[0]) < q0[0].min(q1[0]) || q0[0].max(q1[0]) < p0[0].min(p1[0])
+                || p0[1].max(p1[1]) < q0[1].min(q1[1]) || q0[1].max(q1[1]) < p0[1].min(p1[1]) {
+                continue
+            }
+            let f = |p: Point| [p[0] as f64, p[1] as f64];
+            let (a, b, c, d) = (f(p0), f(p1), f(q0), f(q1));
+            let r = [b[0] - a[0], b[1] - a[1]];
+            let s = [d[0] - c[0], d[1] - c[1]];
+            let ac = [c[0] - a[0], c[1] - a[1]];
+            let den = r[0] * s[1] - r[1] * s[0];
+            let rr = r[0] * r[0] + r[1] * r[1];
+            let ss = s[0] * s[0] + s[1] * s[1];
+
+            if den.abs() <= 1e-12 * (rr * ss).sqrt() {
+                // Parallel: if they are on the same line, each edge is cut
+                // at the ends of the other one that fall inside it.
+                let off = ac[0] * r[1] - ac[1] * r[0];
+                if off.abs() > 1e-9 * rr.sqrt() * rr.sqrt().max(1.0) {
+                    continue
+                }
+                let along = |p: [f64; 2], o: [f64; 2], v: [f64; 2], vv: f64| {
+                    ((p[0] - o[0]) * v[0] + (p[1] - o[1]) * v[1]) / vv
+                };
+                for &q in &[q0, q1] {
+                    let t = along(f(q), a, r, rr);
+                    if t > 0.0 && t < 1.0 {
+                        edges[i].splits.push(q);
+                    }
+                }
+                for &p in &[p0, p1] {
+                    let t = along(f(p), c, s, ss);
+                    if t > 0.0 && t < 1.0 {
+                        edges[j].splits.push(p);
+                    }
+                }
+                continue
+            }
+
+            let t = (ac[0] * s[1] - ac[1] * s[0]) / den;
+            let u = (ac[0] * r[1] - ac[1] * r[0]) / den;
+            if !(0.0..=1.0).contains(&t) || !(0.0..=1.0).contains(&u) {
+                continue
+            }
+            // Crossings at the ends of an edge are snapped to them.
+            let x = [(a[0] + t * r[0]) as f32, (a[1] + t * r[1]) as f32];
+            let x = [p0, p1, q0, q1].iter().cloned()
+                .find(|&e| (e[0] - x[0]).abs() <= 1e-5 * (1.0 + x[0].abs())
+                    && (e[1] - x[1]).abs() <= 1e-5 * (1.0 + x[1].abs()))
+                .unwrap_or(x);
+            if x != p0 && x != p1 {
+                edges[i].splits.push(x);
+            }
+            if x != q0 && x != q1 {
+                edges[j].splits.push(x);
+            }
+        }
+    }
+}
+
+// link joins the pieces, given by their start point, into closed contours.
+// Every point has as many pieces leaving it as arriving, so walking from any
+// piece always comes back to where it started.
+fn link(mut next: HashMap<[u32; 2], Vec<Point>>, count: usize) -> Path {
+    let mut out = Path::new();
+    let mut starts: Vec<Point> = Vec::with_capacity(count);
+    for to in next.values() {
+        starts.extend(to.iter().cloned());
+    }
+    for start in starts {
+        let mut contour = vec![start];
+        let mut p = start;
+        while let Some(q) = next.get_mut(&key(p)).and_then(|to| to.pop()) {
+            contour.push(q);
+            p = q;
+            if p == start {
+                break
+            }
+        }
+        if contour.len() < 4 || contour.last() != Some(&start) {
+            continue
+        }
+        contour.pop();
+        simplify(&mut contour);
+        if contour.len() < 3 {
+            continue
+        }
+        out.move_to(contour[0][0], contour[0][1]);
+        for p in &contour[1..] {
+            out.line_to(p[0], p[1]);
+        }
+        out.close_path();
+    }
+    out
+}
+
+// simplify removes the points of a closed contour that are on the line
+// between their neighbours and keep its direction.
+fn simplify(pts: &mut Vec<Point>) {
+    let mut i = 0;
+    while pts.len() > 2 && i < pts.len() {
+        let n = pts.len();
+        let (a, b, c) = (pts[(i + n - 1) % n], pts[i], pts[(i + 1) % n]);
+        let (ux, uy) = (b[0] as f64 - a[0] as f64, b[1] as f64 - a[1] as f64);
+        let (vx, vy) = (c[0] as f64 - b[0] as f64, c[1] as f64 - b[1] as f64);
+        if ux * vy - uy * vx == 0.0 && ux * vx + uy * vy > 0.0 {
+            pts.remove(i);
+            i = i.saturating_sub(1);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+fn key(p: Point) -> [u32; 2] {
+    // Adding zero turns -0.0 into 0.0, so both have the same key.
+    [(p[0] + 0.0).to_bits(), (p[1] + 0.0).to_bits()]
+}
+
+// crossing returns the winding contribution of the line from a to b to the
+// point p, as in Path::contains.
+fn crossing(a: Point, b: Point, p: [f64; 2]) -> i32 {
+    let (a, b) = ([a[0] as f64, a[1] as f64], [b[0] as f64, b[1] as f64]);
+    if (a[1] <= p[1]) == (b[1] <= p[1]) {
+        return 0
+    }
+    let side = (b[0] - a[0]) * (p[1] - a[1]) - (p[0] - a[0]) * (b[1] - a[1]);
+    if a[1] <= p[1] {
+        if side > 0.0 { 1 } else { 0 }
+    } else if side < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // rand is xorshift32, to make shapes without a dependency.
+    fn rand(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    fn polygon(pts: &[Point]) -> Path {
+        let mut p = Path::new();
+        p.move_to(pts[0][0], pts[0][1]);
+        for q in &pts[1..] {
+            p.line_to(q[0], q[1]);
+        }
+        p.close_path();
+        p
+    }
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> Path {
+        polygon(&[[x0, y0], [x1, y0], [x1, y1], [x0, y1]])
+    }
+
+    // check compares the result of every operation with point sampling of
+    // the inputs, except at points too close to an input line to tell its
+    // side.
+    fn check(a: &Path, b: &Path, rule: FillRule) {
+        let ops = [BoolOp::Union, BoolOp::Intersection, BoolOp::Difference, BoolOp::Xor];
+        let f = |op, a, b| match op {
+            BoolOp::Union => a || b,
+            BoolOp::Intersection => a && b,
+            BoolOp::Difference => a && !b,
+            BoolOp::Xor => a != b,
+        };
+        for &op in &ops {
+            let c = a.boolean(b, op, rule, 0.1);
+            for i in 0..50 {
+                for j in 0..50 {
+                    let (x, y) = (i as f32 * 0.5 + 0.07, j as f32 * 0.5 + 0.11);
+                    let near = |p: &Path| p.distance(x, y, 0.1).is_some_and(|d| d < 1e-3);
+                    if near(a) || near(b) {
+                        continue
+                    }
+                    let want = f(op, a.contains(x, y, rule, 0.1), b.contains(x, y, rule, 0.1));
+                    assert_eq!(c.contains(x, y, FillRule::NonZero, 0.1), want, "{:?} at ({}, {})", op, x, y);
+                    assert_eq!(c.contains(x, y, FillRule::EvenOdd, 0.1), want, "{:?} at ({}, {})", op, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_shapes() {
+        let mut state = 7;
+        let mut coord = || (rand(&mut state) % 24) as f32;
+        for _ in 0..12 {
+            let (x0, y0, x1, y1) = (coord(), coord(), coord(), coord());
+            let a = rect(x0.min(x1), y0.min(y1), x0.max(x1) + 1.0, y0.max(y1) + 1.0);
+            let b = polygon(&[[coord(), coord()], [coord(), coord()], [coord(), coord()]]);
+            check(&a, &b, FillRule::NonZero);
+            check(&b, &a, FillRule::EvenOdd);
+        }
+    }
+
+    #[test]
+    fn touching_edges() {
+        // Side by side, sharing an edge.
+        check(&rect(2.0, 2.0, 10.0, 10.0), &rect(10.0, 2.0, 18.0, 10.0), FillRule::NonZero);
+        // Sharing part of an edge.
+        check(&rect(2.0, 2.0, 10.0, 10.0), &rect(10.0, 6.0, 18.0, 14.0), FillRule::NonZero);
+        // Touching at a corner.
+        check(&rect(2.0, 2.0, 10.0, 10.0), &rect(10.0, 10.0, 18.0, 18.0), FillRule::NonZero);
+    }
+
+    #[test]
+    fn overlapping_edges() {
+        // The same square twice, and turned the other way.
+        let a = rect(4.0, 4.0, 12.0, 12.0);
+        check(&a, &a, FillRule::NonZero);
+        check(&a, &polygon(&[[4.0, 4.0], [4.0, 12.0], [12.0, 12.0], [12.0, 4.0]]), FillRule::NonZero);
+        // Inside, sharing two edges.
+        check(&a, &rect(4.0, 4.0, 8.0, 8.0), FillRule::NonZero);
+        // Overlapping, with collinear edges.
+        check(&a, &rect(8.0, 4.0, 16.0, 12.0), FillRule::EvenOdd);
+        // A triangle with its hypotenuse on the diagonal of the square.
+        check(&a, &polygon(&[[4.0, 4.0], [12.0, 12.0], [12.0, 4.0]]), FillRule::NonZero);
+    }
+
+    #[test]
+    fn self_intersecting() {
+        // A bow tie, whose center counts once with either rule.
+        let bow = polygon(&[[2.0, 2.0], [20.0, 20.0], [20.0, 2.0], [2.0, 20.0]]);
+        check(&bow, &rect(6.0, 6.0, 16.0, 16.0), FillRule::NonZero);
+        // A square wound twice, which the even-odd rule leaves empty.
+        let twice = polygon(&[[4.0, 4.0], [12.0, 4.0], [12.0, 12.0], [4.0, 12.0], [4.0, 4.0], [12.0, 4.0], [12.0, 12.0], [4.0, 12.0]]);
+        check(&twice, &rect(8.0, 8.0, 16.0, 16.0), FillRule::NonZero);
+        check(&twice, &rect(8.0, 8.0, 16.0, 16.0), FillRule::EvenOdd);
+    }
+}
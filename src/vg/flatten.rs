@@ -0,0 +1,176 @@
+// This file contains the flattening of Bézier curves into lines. The
+// Rasterizer and everything that has to match what it fills, like stroking
+// and measuring, go through it.
+
+use super::{dev_squared, lerp};
+use super::path::{Path, Segment};
+
+/// The tolerance that the Rasterizer uses by default: the largest distance,
+/// in pixels, between a curve and the lines that replace it.
+///
+/// It is 1/(4√3), which is what the constants of the Go rasterizer that this
+/// package ports amount to.
+pub const DEFAULT_TOLERANCE: f32 = 0.144_337_57;
+
+// MIN_TOLERANCE keeps the number of lines finite.
+pub(super) const MIN_TOLERANCE: f32 = 1e-3;
+
+/// Returns the number of lines that approximate the quadratic Bézier curve
+/// from `a` via `b` to `c` within `tolerance`.
+pub fn quad_count(a: [f32; 2], b: [f32; 2], c: [f32; 2], tolerance: f32) -> usize {
+    count(dev_squared(a[0], a[1], b[0], b[1], c[0], c[1]), tolerance)
+}
+
+/// Returns the number of lines that approximate the cubic Bézier curve
+/// from `a` via `b` and `c` to `d` within `tolerance`.
+pub fn cube_count(a: [f32; 2], b: [f32; 2], c: [f32; 2], d: [f32; 2], tolerance: f32) -> usize {
+    let devsq = dev_squared(a[0], a[1], b[0], b[1], d[0], d[1]);
+    let devsq_alt = dev_squared(a[0], a[1], c[0], c[1], d[0], d[1]);
+    count(devsq.max(devsq_alt), tolerance)
+}
+
+// count returns the number of lines for a curve whose dev_squared is devsq.
+//
+// A single line is off a quadratic curve by at most |a - 2b + c| / 4, and n
+// evenly spaced lines by that over n², so (devsq / (16 tol²))^(1/4) lines are
+// enough. Cubic curves use the larger estimate of their two control points.
+fn count(devsq: f32, tolerance: f32) -> usize {
+    let tol = tolerance.max(MIN_TOLERANCE);
+    let tol_sq = 16.0 * tol * tol;
+    if devsq < tol_sq || devsq.is_nan() {
+        return 1
+    }
+    1 + (devsq as f64 / tol_sq as f64).sqrt().sqrt() as usize
+}
+
+/// Returns the ends of the lines that approximate the quadratic Bézier curve
+/// from `a` via `b` to `c`. The last one is `c`.
+pub fn quad(a: [f32; 2], b: [f32; 2], c: [f32; 2], tolerance: f32) -> impl Iterator<Item = [f32; 2]> {
+    let n = quad_count(a, b, c, tolerance);
+    (1..=n).map(move |i| if i == n { c } else { quad_point(a, b, c, i as f32 / n as f32) })
+}
+
+/// Returns the ends of the lines that approximate the cubic Bézier curve
+/// from `a` via `b` and `c` to `d`. The last one is `d`.
+pub fn cube(a: [f32; 2], b: [f32; 2], c: [f32; 2], d: [f32; 2], tolerance: f32) -> impl Iterator<Item = [f32; 2]> {
+    let n = cube_count(a, b, c, d, tolerance);
+    (1..=n).map(move |i| if i == n { d } else { cube_point(a, b, c, d, i as f32 / n as f32) })
+}
+
+fn quad_point(a: [f32; 2], b: [f32; 2], c: [f32; 2], t: f32) -> [f32; 2] {
+    let (abx, aby) = lerp(t, a[0], a[1], b[0], b[1]);
+    let (bcx, bcy) = lerp(t, b[0], b[1], c[0], c[1]);
+    let (x, y) = lerp(t, abx, aby, bcx, bcy);
+    [x, y]
+}
+
+fn cube_point(a: [f32; 2], b: [f32; 2], c: [f32; 2], d: [f32; 2], t: f32) -> [f32; 2] {
+    let (abx, aby) = lerp(t, a[0], a[1], b[0], b[1]);
+    let (bcx, bcy) = lerp(t, b[0], b[1], c[0], c[1]);
+    let (cdx, cdy) = lerp(t, c[0], c[1], d[0], d[1]);
+    let (abcx, abcy) = lerp(t, abx, aby, bcx, bcy);
+    let (bcdx, bcdy) = lerp(t, bcx, bcy, cdx, cdy);
+    let (x, y) = lerp(t, abcx, abcy, bcdx, bcdy);
+    [x, y]
+}
+
+/// Iterator over the segments of a path with its curves replaced by lines,
+/// returned by `Path::flatten`.
+///
+/// It only yields `MoveTo`, `LineTo` and `Close`.
+#[derive(Clone, Debug)]
+pub struct Flatten<'a> {
+    segs: std::slice::Iter<'a, Segment>,
+    tolerance: f32,
+    pen: [f32; 2],
+    first: [f32; 2],
+    // curve is the curve being flattened, with the point it starts from,
+    // the number of lines it takes and the number already yielded.
+    curve: Option<(Segment, [f32; 2], usize, usize)>,
+}
+
+impl<'a> Iterator for Flatten<'a> {
+    type Item = Segment;
+
+    fn next(&mut self) -> Option<Segment> {
+        if let Some((seg, a, n, ref mut i)) = self.curve {
+            *i += 1;
+            let p = match seg {
+                _ if *i == n => seg.end().unwrap(),
+                Segment::QuadTo(b, c) => quad_point(a, b, c, *i as f32 / n as f32),
+                Segment::CubeTo(b, c, d) => cube_point(a, b, c, d, *i as f32 / n as f32),
+                _ => unreachable!(),
+            };
+            if *i == n {
+                self.curve = None;
+            }
+            self.pen = p;
+            return Some(Segment::LineTo(p))
+        }
+
+        let seg = *self.segs.next()?;
+        match seg {
+            Segment::MoveTo(p) => {
+                self.first = p;
+                self.pen = p;
+            }
+            Segment::LineTo(p) => self.pen = p,
+            Segment::Close => self.pen = self.first,
+            Segment::QuadTo(b, c) => {
+                let n = quad_count(self.pen, b, c, self.tolerance);
+                self.curve = Some((seg, self.pen, n, 0));
+                return self.next()
+            }
+            Segment::CubeTo(b, c, d) => {
+                let n = cube_count(self.pen, b, c, d, self.tolerance);
+                self.curve = Some((seg, self.pen, n, 0));
+                return self.next()
+            }
+        }
+        Some(seg)
+    }
+}
+
+impl Path {
+    /// Returns the segments of the path with its curves replaced by lines
+    /// that are at most `tolerance` away from them.
+    ///
+    /// For the lines that the Rasterizer fills, pass its `tolerance`.
+    pub fn flatten(&self, tolerance: f32) -> Flatten<'_> {
+        Flatten {
+            segs: self.iter(),
+            tolerance,
+            pen: [0.0, 0.0],
+            first: [0.0, 0.0],
+            curve: None,
+        }
+    }
+}
+
+// polylines flattens every subpath of the path and drops repeated points.
+// The flag tells whether the subpath is closed.
+pub(super) fn polylines(path: &Path, tolerance: f32) -> Vec<(Vec<[f32; 2]>, bool)> {
+    let mut out: Vec<(Vec<[f32; 2]>, bool)> = Vec::new();
+    for seg in path.flatten(tolerance) {
+        match seg {
+            Segment::MoveTo(p) => out.push((vec![p], false)),
+            Segment::LineTo(p) => {
+                if let Some((line, _)) = out.last_mut() {
+                    if line.last() != Some(&p) {
+                        line.push(p);
+                    }
+                }
+            }
+            Segment::Close => {
+                if let Some((line, closed)) = out.last_mut() {
+                    if line.len() > 1 && line.last() == line.first() {
+                        line.pop();
+                    }
+                    *closed = line.len() > 1;
+                }
+            }
+            Segment::QuadTo(..) | Segment::CubeTo(..) => unreachable!(),
+        }
+    }
+    out
+}
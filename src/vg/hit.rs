@@ -0,0 +1,96 @@
+// This file contains hit testing against the fill and the stroke of a path.
+//
+// Paths are flattened like the Rasterizer flattens them, so that a point hits
+// a shape exactly when it is inside the lines that are drawn.
+
+use super::FillRule;
+use super::flatten::polylines;
+use super::path::Path;
+use super::stroke::Stroke;
+
+impl Path {
+    /// Returns whether the point (x, y) is inside the path filled with `rule`.
+    ///
+    /// Open subpaths are closed, as filling requires. Pass the Rasterizer's
+    /// tolerance to match what it draws, and test pixels at their centers,
+    /// (x + 0.5, y + 0.5).
+    pub fn contains(&self, x: f32, y: f32, rule: FillRule, tolerance: f32) -> bool {
+        let mut winding = 0;
+        for (pts, _) in polylines(self, tolerance) {
+            for i in 0..pts.len() {
+                let (p, q) = (pts[i], pts[(i + 1) % pts.len()]);
+                winding += crossing(p, q, x, y);
+            }
+        }
+        match rule {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+
+    /// Returns the distance from the point (x, y) to the nearest line of the
+    /// path, or `None` for a path without lines.
+    ///
+    /// Closed subpaths include the line back to their first point.
+    pub fn distance(&self, x: f32, y: f32, tolerance: f32) -> Option<f32> {
+        let mut best: Option<f32> = None;
+        for (pts, closed) in polylines(self, tolerance) {
+            if pts.len() < 2 {
+                continue
+            }
+            let n = if closed { pts.len() } else { pts.len() - 1 };
+            for i in 0..n {
+                let d = segment_distance(pts[i], pts[(i + 1) % pts.len()], x, y);
+                best = Some(best.map_or(d, |b| b.min(d)));
+            }
+        }
+        best.map(f32::sqrt)
+    }
+
+    /// Returns whether the point (x, y) is on the path stroked with `stroke`,
+    /// or within `slop` of it, which makes thin lines easier to pick.
+    ///
+    /// The stroke is exactly what `Path::stroke` outlines, with its joins
+    /// and caps.
+    pub fn stroke_contains(&self, x: f32, y: f32, stroke: &Stroke, slop: f32) -> bool {
+        let outline = self.stroke(stroke);
+        if outline.contains(x, y, FillRule::NonZero, stroke.tolerance) {
+            return true
+        }
+        // Outside of every piece of the outline, the nearest of their edges
+        // is on the outer boundary.
+        slop > 0.0 && outline.distance(x, y, stroke.tolerance).is_some_and(|d| d <= slop)
+    }
+}
+
+// crossing returns the winding contribution of the line from p to q to the
+// point (x, y): ±1 when the line crosses the horizontal ray to the right of
+// the point, and 0 otherwise.
+//
+// Lines include the end with the smaller y and exclude the other, so that a ray
+// through a vertex counts it once.
+fn crossing(p: [f32; 2], q: [f32; 2], x: f32, y: f32) -> i32 {
+    if (p[1] <= y) == (q[1] <= y) {
+        return 0
+    }
+    // The x of the line at y, compared without dividing.
+    let side = (q[0] - p[0]) * (y - p[1]) - (x - p[0]) * (q[1] - p[1]);
+    if p[1] <= y {
+        if side > 0.0 { 1 } else { 0 }
+    } else if side < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+// segment_distance returns the squared distance from (x, y) to the line from
+// p to q.
+fn segment_distance(p: [f32; 2], q: [f32; 2], x: f32, y: f32) -> f32 {
+    let (dx, dy) = (q[0] - p[0], q[1] - p[1]);
+    let (px, py) = (x - p[0], y - p[1]);
+    let len = dx * dx + dy * dy;
+    let t = if len > 0.0 { ((px * dx + py * dy) / len).clamp(0.0, 1.0) } else { 0.0 };
+    let (ex, ey) = (px - t * dx, py - t * dy);
+    ex * ex + ey * ey
+}
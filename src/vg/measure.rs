@@ -0,0 +1,199 @@
+// This file contains measurements along a path: its length, the point at a
+// distance, sub-paths between two distances and text laid along the path.
+//
+// Curves are flattened like the Rasterizer flattens them, so distances are
+// exact for the lines that end up filled.
+
+use rusttype::{Font, Scale, Segment as GlyphSegment};
+
+use super::flatten::{polylines, DEFAULT_TOLERANCE};
+use super::path::Path;
+
+/// A path flattened for measuring.
+///
+/// Subpaths are measured one after the other, as if they were joined, and
+/// closed subpaths include the line back to their first point.
+#[derive(Clone, Debug)]
+pub struct Measure {
+    contours: Vec<Contour>,
+    length: f32,
+}
+
+#[derive(Clone, Debug)]
+struct Contour {
+    /// Closed contours repeat their first point at the end.
+    pts: Vec<[f32; 2]>,
+    closed: bool,
+    /// Distance of each point from the start of the path.
+    dist: Vec<f32>,
+}
+
+impl Measure {
+    pub fn new(path: &Path) -> Self {
+        Self::with_tolerance(path, DEFAULT_TOLERANCE)
+    }
+
+    /// Measures the path with its curves flattened within `tolerance`.
+    pub fn with_tolerance(path: &Path, tolerance: f32) -> Self {
+        let mut contours = Vec::new();
+        let mut length = 0.0;
+        for (mut pts, closed) in polylines(path, tolerance) {
+            if pts.len() < 2 {
+                continue
+            }
+            if closed {
+                pts.push(pts[0]);
+            }
+            let mut dist = Vec::with_capacity(pts.len());
+            dist.push(length);
+            for w in pts.windows(2) {
+                length += distance(w[0], w[1]);
+                dist.push(length);
+            }
+            contours.push(Contour { pts, closed, dist });
+        }
+        Self { contours, length }
+    }
+
+    /// Returns the total length of the subpaths.
+    pub fn length(&self) -> f32 { self.length }
+
+    /// Returns the point at the distance `d` from the start, and the unit
+    /// tangent there, or `None` for a path without length.
+    ///
+    /// Distances out of range are clamped to the ends of the path.
+    pub fn pos_tan(&self, d: f32) -> Option<([f32; 2], [f32; 2])> {
+        let d = d.clamp(0.0, self.length);
+        let c = self.contours.iter()
+            .find(|c| d <= c.dist[c.dist.len() - 1])
+            .or_else(|| self.contours.last())?;
+
+        // The segment that holds d, skipping those of zero length so that
+        // the tangent is always defined.
+        let i = match c.dist.iter().position(|&v| v >= d) {
+            Some(0) | None => 1,
+            Some(i) => i,
+        };
+        let i = (i..c.pts.len())
+            .find(|&i| c.dist[i] > c.dist[i - 1])
+            .or_else(|| (1..i).rev().find(|&i| c.dist[i] > c.dist[i - 1]))?;
+
+        let (p, q) = (c.pts[i - 1], c.pts[i]);
+        let len = c.dist[i] - c.dist[i - 1];
+        let t = ((d - c.dist[i - 1]) / len).clamp(0.0, 1.0);
+        let pos = [p[0] + (q[0] - p[0]) * t, p[1] + (q[1] - p[1]) * t];
+        let tan = [(q[0] - p[0]) / len, (q[1] - p[1]) / len];
+        Some((pos, tan))
+    }
+
+    /// Returns the part of the path between the distances `start` and `end`,
+    /// as lines.
+    ///
+    /// Each subpath that the range touches gives a subpath of the result, and
+    /// closed subpaths that are wholly in the range stay closed.
+    pub fn segment(&self, start: f32, end: f32) -> Path {
+        let mut out = Path::new();
+        let (start, end) = (start.max(0.0), end.min(self.length));
+        if start >= end {
+            return out
+        }
+        for c in &self.contours {
+            let (c0, c1) = (c.dist[0], c.dist[c.dist.len() - 1]);
+            if c1 < start || c0 > end {
+                continue
+            }
+            let at = |d: f32| {
+                let i = c.dist.iter().position(|&v| v >= d).unwrap_or(c.pts.len() - 1).max(1);
+                let (p, q) = (c.pts[i - 1], c.pts[i]);
+                let len = c.dist[i] - c.dist[i - 1];
+                let t = if len > 0.0 { (d - c.dist[i - 1]) / len } else { 1.0 };
+                [p[0] + (q[0] - p[0]) * t, p[1] + (q[1] - p[1]) * t]
+            };
+
+            let (a, b) = (start.max(c0), end.min(c1));
+            let p = at(a);
+            out.move_to(p[0], p[1]);
+            for (i, &p) in c.pts.iter().enumerate() {
+                if c.dist[i] > a && c.dist[i] < b {
+                    out.line_to(p[0], p[1]);
+                }
+            }
+            let p = at(b);
+            out.line_to(p[0], p[1]);
+            if c.closed && a == c0 && b == c1 {
+                out.close_path();
+            }
+        }
+        out
+    }
+
+    /// Lays `text` along the path and returns the outlines of its glyphs,
+    /// to be filled with the non-zero rule.
+    ///
+    /// The baseline follows the path from the distance `offset`, and each
+    /// glyph is turned to the tangent at its middle. Glyphs that would end
+    /// past the end of the path are left out.
+    pub fn text(&self, font: &Font, scale: f32, offset: f32, text: &str) -> Path {
+        let scale = Scale::uniform(scale);
+        let mut out = Path::new();
+        let mut d = offset;
+        let mut prev = None;
+        for glyph in font.glyphs_for(text.chars()) {
+            let glyph = glyph.scaled(scale);
+            if let Some(id) = prev {
+                d += font.pair_kerning(scale, id, glyph.id());
+            }
+            prev = Some(glyph.id());
+
+            let advance = glyph.h_metrics().advance_width;
+            let mid = d + advance / 2.0;
+            d += advance;
+            if mid < 0.0 {
+                continue
+            }
+            if d > self.length {
+                break
+            }
+            let (shape, (pos, tan)) = match (glyph.shape(), self.pos_tan(mid)) {
+                (Some(shape), Some(pt)) => (shape, pt),
+                _ => continue,
+            };
+
+            // Glyph outlines have their origin on the baseline and y up. They
+            // are flipped and moved so that x = 0 is the middle of the glyph.
+            let m = |p: rusttype::Point<f32>| {
+                let (x, y) = (p.x - advance / 2.0, -p.y);
+                [pos[0] + tan[0] * x - tan[1] * y, pos[1] + tan[1] * x + tan[0] * y]
+            };
+            for contour in shape {
+                for (i, seg) in contour.segments.iter().enumerate() {
+                    let first = match *seg {
+                        GlyphSegment::Line(l) => l.p[0],
+                        GlyphSegment::Curve(c) => c.p[0],
+                    };
+                    if i == 0 {
+                        let p = m(first);
+                        out.move_to(p[0], p[1]);
+                    }
+                    match *seg {
+                        GlyphSegment::Line(l) => {
+                            let p = m(l.p[1]);
+                            out.line_to(p[0], p[1]);
+                        }
+                        GlyphSegment::Curve(c) => {
+                            let (b, p) = (m(c.p[1]), m(c.p[2]));
+                            out.quad_to(b[0], b[1], p[0], p[1]);
+                        }
+                    }
+                }
+                out.close_path();
+            }
+        }
+        out
+    }
+}
+
+fn distance(p: [f32; 2], q: [f32; 2]) -> f32 {
+    let (dx, dy) = (q[0] - p[0], q[1] - p[1]);
+    (dx * dx + dy * dy).sqrt()
+}
@@ -1,12 +1,34 @@
 pub mod raster_floating;
 pub mod raster_fixed;
 pub mod vector;
+pub mod paint;
+pub mod simd;
+pub mod path;
+pub mod flatten;
+pub mod arc;
+pub mod svg;
+pub mod stroke;
+pub mod measure;
+pub mod hit;
+pub mod boolean;
+
+use crate::image::{Point, Rectangle};
 
 pub enum Op {
     Over,
     Src,
 }
 
+/// Rule deciding which points are inside a path with overlapping or nested contours.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FillRule {
+    /// Inside where the contours wind around the point a non-zero number of times.
+    NonZero,
+    /// Inside where an odd number of contours surround the point,
+    /// regardless of their direction.
+    EvenOdd,
+}
+
 // Raster is a 2-D vector graphics rasterizer.
 //
 // The zero value is usable, in that it is a Rasterizer whose rendered mask
@@ -26,12 +48,28 @@ pub struct Rasterizer {
     //	bufU32[i] = math.Float32bits(x + math.Float32frombits(bufU32[i]))
     //
     // See golang.org/issue/17220 for some discussion.
+    //
+    // Only the cells of area are stored, row by row, so that the memory
+    // follows the size of the path rather than the bounds.
 
     buf: SimdVec,
 
+    // area holds the cells stored in buf. It contains dirty, and grows with
+    // it.
+    area: Rectangle,
+
     use_fpm: bool,
 
     size: [usize; 2],
+
+    // dirty holds the cells that the path may have touched. Every cell outside
+    // of it is zero, so accumulating, clearing and compositing can skip them.
+    dirty: Rectangle,
+
+    // accumulated is set once buf holds the coverage instead of the area
+    // values, so that the path can be composited any number of times.
+    accumulated: bool,
+
     first: [f32; 2],
     pen: [f32; 2],
 
@@ -40,6 +78,17 @@ pub struct Rasterizer {
     // The zero value is draw.Over.
     pub draw_op: Op,
 
+    /// FillRule decides which areas of overlapping contours are covered.
+    ///
+    /// The default is non-zero.
+    pub fill_rule: FillRule,
+
+    /// Tolerance is the largest distance, in pixels, between a curve and the
+    /// lines that quad_to and cube_to replace it with.
+    ///
+    /// The default is flatten::DEFAULT_TOLERANCE.
+    pub tolerance: f32,
+
     // TODO: an exported field equivalent to the mask point in the
     // draw.DrawMask function in the stdlib image/draw package?
 }
@@ -111,6 +160,100 @@ fn lerp(t: f32, px: f32, py: f32, qx: f32, qy: f32) -> (f32, f32) {
     (px + t * (qx - px), py + t * (qy - py))
 }
 
+/// Converts 8-bit color components to the 16-bit ones used for compositing.
+#[inline(always)]
+fn widen(c: [u8; 4]) -> [u32; 4] {
+    [c[0] as u32 * 0x101, c[1] as u32 * 0x101, c[2] as u32 * 0x101, c[3] as u32 * 0x101]
+}
+
+// Row is a row of the stored cells, indexed by x like a row of the bounds.
+struct Row<'a, T> {
+    cells: &'a mut [T],
+    // x0 is the x of the first stored cell.
+    x0: usize,
+}
+
+impl<T> Row<'_, T> {
+    // len returns the x right of the last stored cell. The line_to methods
+    // skip the cells from there, which they only reach by clamping to the
+    // width: those cells change no coverage inside of the bounds.
+    fn len(&self) -> usize { self.x0 + self.cells.len() }
+}
+
+impl<T> std::ops::Index<usize> for Row<'_, T> {
+    type Output = T;
+    fn index(&self, x: usize) -> &T { &self.cells[x - self.x0] }
+}
+
+impl<T> std::ops::IndexMut<usize> for Row<'_, T> {
+    fn index_mut(&mut self, x: usize) -> &mut T { &mut self.cells[x - self.x0] }
+}
+
+impl Rasterizer {
+    fn row_u32(&mut self, y: i32, x0: i32, x1: i32) -> Row<'_, u32> {
+        let (cells, x0) = self.touch(y, x0, x1);
+        Row { cells: &mut self.buf.as_u32()[cells], x0 }
+    }
+
+    fn row_f32(&mut self, y: i32, x0: i32, x1: i32) -> Row<'_, f32> {
+        let (cells, x0) = self.touch(y, x0, x1);
+        Row { cells: &mut self.buf.as_f32()[cells], x0 }
+    }
+
+    // touch grows the dirty rectangle by the cells x0 to x1 (inclusive) of row
+    // y, clamped like the line_to methods clamp them, and makes sure that they
+    // are stored. It returns the range of buf holding the row, and the x of its
+    // first cell.
+    //
+    // A cell clamped to the width is not stored, but the coverage then runs up
+    // to the right edge, so the rectangle takes the last cell of the row.
+    fn touch(&mut self, y: i32, x0: i32, x1: i32) -> (std::ops::Range<usize>, usize) {
+        let w = self.size[0] as i32;
+        let (x0, x1) = (clamp(x0, w) as isize, clamp(x1, w) as isize);
+        let w = w as isize;
+        let (x0, x1) = if x1 == w { (x0.min(w - 1).max(0), w) } else { (x0, x1 + 1) };
+        let y = y as isize;
+        let r = Rectangle { min: Point { x: x0, y }, max: Point { x: x1, y: y + 1 } };
+        if r.is_empty() {
+            return (0..0, 0)
+        }
+        self.dirty = self.dirty.union(r);
+        if !r.in_rect(&self.area) {
+            self.grow(r);
+        }
+        let a = self.area;
+        let start = ((y - a.min.y) * a.dx()) as usize;
+        (start..start + a.dx() as usize, a.min.x as usize)
+    }
+
+    // grow makes the stored cells cover r. The area grows by half of its
+    // size in the directions that it has to, so that the cells of a path
+    // drawn segment by segment are only copied a few times.
+    fn grow(&mut self, r: Rectangle) {
+        let old = self.area;
+        let mut a = old.union(r);
+        if !old.is_empty() {
+            let (dx, dy) = (a.dx() / 2, a.dy() / 2);
+            if a.min.x < old.min.x { a.min.x -= dx }
+            if a.max.x > old.max.x { a.max.x += dx }
+            if a.min.y < old.min.y { a.min.y -= dy }
+            if a.max.y > old.max.y { a.max.y += dy }
+            a = a.intersect(self.bounds());
+        }
+
+        let mut buf = SimdVec::new((a.dx() * a.dy()) as usize);
+        let (src, dst) = (self.buf.as_slice_u32(), buf.as_u32());
+        let (ow, nw) = (old.dx() as usize, a.dx() as usize);
+        for y in old.min.y..old.max.y {
+            let s = (y - old.min.y) as usize * ow;
+            let d = (y - a.min.y) as usize * nw + (old.min.x - a.min.x) as usize;
+            dst[d..d + ow].copy_from_slice(&src[s..s + ow]);
+        }
+        self.buf = buf;
+        self.area = a;
+    }
+}
+
 #[inline(always)]
 fn clamp(i: i32, width: i32) -> usize {
     if i < 0 {
@@ -0,0 +1,308 @@
+// This file contains paint sources for filling rasterized paths.
+
+use super::{widen, Op, Rasterizer};
+use crate::geom::Transform;
+use crate::image::{RGBA, Rectangle};
+
+/// How a gradient continues outside of the [0, 1] range of its stops.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Spread {
+    /// Extends the first and the last colors.
+    Pad,
+    /// Starts over from the first stop.
+    Repeat,
+    /// Goes back and forth.
+    Reflect,
+}
+
+/// A gradient color stop.
+///
+/// Colors are alpha-premultiplied 16-bit values, as for `Rasterizer::rgba_uniform_over`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Stop {
+    pub offset: f32,
+    pub color: [u32; 4],
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shape {
+    /// Colors change along the line from `start` to `end`.
+    Linear { start: [f32; 2], end: [f32; 2] },
+    /// Two-point conical gradient, as in the HTML canvas:
+    /// the first stop is at the circle (`c0`, `r0`), the last one at (`c1`, `r1`).
+    ///
+    /// A zero `r0` with `c0` inside the second circle gives a focal gradient.
+    Radial { c0: [f32; 2], r0: f32, c1: [f32; 2], r1: f32 },
+    /// Colors sweep clockwise around `center`, starting at `angle` radians.
+    Conic { center: [f32; 2], angle: f32 },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient {
+    pub shape: Shape,
+    /// Sorted by offset.
+    pub stops: Vec<Stop>,
+    pub spread: Spread,
+    /// Maps gradient space to path space,
+    /// as `[a, b, c, d, e, f]` for `x' = a*x + c*y + e` and `y' = b*x + d*y + f`.
+    pub transform: [f32; 6],
+}
+
+pub const IDENTITY: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+impl Gradient {
+    pub fn linear(start: [f32; 2], end: [f32; 2]) -> Self {
+        Self::new(Shape::Linear { start, end })
+    }
+
+    pub fn radial(c0: [f32; 2], r0: f32, c1: [f32; 2], r1: f32) -> Self {
+        Self::new(Shape::Radial { c0, r0, c1, r1 })
+    }
+
+    pub fn conic(center: [f32; 2], angle: f32) -> Self {
+        Self::new(Shape::Conic { center, angle })
+    }
+
+    fn new(shape: Shape) -> Self {
+        Self { shape, stops: Vec::new(), spread: Spread::Pad, transform: IDENTITY }
+    }
+
+    /// Adds a color stop, keeping the stops sorted.
+    pub fn stop(mut self, offset: f32, color: [u32; 4]) -> Self {
+        let i = self.stops.iter().rposition(|s| s.offset <= offset).map_or(0, |i| i + 1);
+        self.stops.insert(i, Stop { offset, color });
+        self
+    }
+
+    pub fn spread(mut self, spread: Spread) -> Self {
+        self.spread = spread;
+        self
+    }
+
+    /// Sets the transform, as an array or a `geom::Transform`.
+    pub fn transform<M: Into<[f32; 6]>>(mut self, m: M) -> Self {
+        self.transform = m.into();
+        self
+    }
+
+    /// Returns the gradient parameter at a point in gradient space,
+    /// or `None` where a radial gradient is undefined.
+    fn param(&self, x: f32, y: f32) -> Option<f32> {
+        match self.shape {
+            Shape::Linear { start, end } => {
+                let (dx, dy) = (end[0] - start[0], end[1] - start[1]);
+                let len = dx * dx + dy * dy;
+                if len == 0.0 {
+                    return None;
+                }
+                Some(((x - start[0]) * dx + (y - start[1]) * dy) / len)
+            }
+            Shape::Radial { c0, r0, c1, r1 } => {
+                // Solve |p - c(t)| = r(t) for the largest t with r(t) >= 0, where
+                // c(t) = c0 + t*(c1 - c0) and r(t) = r0 + t*(r1 - r0).
+                let (cdx, cdy, dr) = (c1[0] - c0[0], c1[1] - c0[1], r1 - r0);
+                let (pdx, pdy) = (x - c0[0], y - c0[1]);
+                let a = cdx * cdx + cdy * cdy - dr * dr;
+                let b = pdx * cdx + pdy * cdy + r0 * dr;
+                let c = pdx * pdx + pdy * pdy - r0 * r0;
+                let valid = |t: f32| r0 + t * dr >= 0.0;
+
+                if a.abs() < 1e-6 {
+                    if b == 0.0 {
+                        return None;
+                    }
+                    let t = c / (2.0 * b);
+                    return if valid(t) { Some(t) } else { None };
+                }
+                let disc = b * b - a * c;
+                if disc < 0.0 {
+                    return None;
+                }
+                let sq = disc.sqrt();
+                let (t0, t1) = ((b + sq) / a, (b - sq) / a);
+                let (hi, lo) = if t0 > t1 { (t0, t1) } else { (t1, t0) };
+                if valid(hi) {
+                    Some(hi)
+                } else if valid(lo) {
+                    Some(lo)
+                } else {
+                    None
+                }
+            }
+            Shape::Conic { center, angle } => {
+                const PI2: f32 = std::f32::consts::PI * 2.0;
+                let a = (y - center[1]).atan2(x - center[0]) - angle;
+                Some((a / PI2).rem_euclid(1.0))
+            }
+        }
+    }
+
+    fn lut(&self) -> Vec<[u32; 4]> {
+        (0..LUT_SIZE)
+            .map(|i| self.color_at(i as f32 / (LUT_SIZE - 1) as f32))
+            .collect()
+    }
+
+    fn color_at(&self, t: f32) -> [u32; 4] {
+        let stops = &self.stops;
+        match stops.len() {
+            0 => return [0; 4],
+            1 => return stops[0].color,
+            _ => (),
+        }
+        if t <= stops[0].offset {
+            return stops[0].color;
+        }
+        for w in stops.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            if t <= b.offset {
+                let span = b.offset - a.offset;
+                let k = if span <= 0.0 { 1.0 } else { (t - a.offset) / span };
+                return std::array::from_fn(|i| {
+                    (a.color[i] as f32 + (b.color[i] as f32 - a.color[i] as f32) * k) as u32
+                });
+            }
+        }
+        stops[stops.len() - 1].color
+    }
+}
+
+const LUT_SIZE: usize = 256;
+
+/// Source of colors for filling paths.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Paint {
+    /// Alpha-premultiplied 16-bit color.
+    Solid([u32; 4]),
+    Gradient(Gradient),
+}
+
+/// A paint prepared for sampling.
+struct Shader<'a> {
+    gradient: &'a Gradient,
+    inverse: Transform,
+    lut: Vec<[u32; 4]>,
+}
+
+impl<'a> Shader<'a> {
+    fn new(gradient: &'a Gradient) -> Option<Self> {
+        Some(Self {
+            gradient,
+            inverse: Transform::from(gradient.transform).inverse()?,
+            lut: gradient.lut(),
+        })
+    }
+
+    /// Returns the color at the center of the pixel (x, y) in path space.
+    fn at(&self, x: isize, y: isize) -> [u32; 4] {
+        let [gx, gy] = self.inverse.apply([x as f32 + 0.5, y as f32 + 0.5]);
+        let t = match self.gradient.param(gx, gy) {
+            Some(t) if t.is_finite() => t,
+            _ => return [0; 4],
+        };
+        let t = match self.gradient.spread {
+            Spread::Pad => t.clamp(0.0, 1.0),
+            Spread::Repeat => t.rem_euclid(1.0),
+            Spread::Reflect => {
+                let t = t.rem_euclid(2.0);
+                if t > 1.0 { 2.0 - t } else { t }
+            }
+        };
+        self.lut[(t * (LUT_SIZE - 1) as f32 + 0.5) as usize]
+    }
+}
+
+impl Rasterizer {
+    /// Fills the accumulated path with `paint`, using `draw_op` to combine it with `dst`.
+    ///
+    /// Paint coordinates are in the path space: (0, 0) maps to `r.min`.
+    pub fn draw_paint(&mut self, dst: &mut RGBA, r: Rectangle, paint: &Paint) {
+        match (paint, &self.draw_op) {
+            (Paint::Solid(color), Op::Over) => self.rgba_uniform_over(dst, r, *color),
+            (Paint::Solid(color), Op::Src) => self.rgba_uniform_src(dst, r, *color),
+            (Paint::Gradient(g), Op::Over) => self.gradient_over(dst, r, g),
+            (Paint::Gradient(g), Op::Src) => self.gradient_src(dst, r, g),
+        }
+    }
+
+    pub fn gradient_over(&mut self, dst: &mut RGBA, r: Rectangle, gradient: &Gradient) {
+        if let Some(shader) = Shader::new(gradient) {
+            self.shade_over(dst, r, |x, y| shader.at(x, y))
+        }
+    }
+
+    pub fn gradient_src(&mut self, dst: &mut RGBA, r: Rectangle, gradient: &Gradient) {
+        if let Some(shader) = Shader::new(gradient) {
+            self.shade_src(dst, r, |x, y| shader.at(x, y))
+        }
+    }
+
+    /// Fills the accumulated path with `pattern`, using `draw_op` to combine it with `dst`.
+    ///
+    /// Pattern coordinates are in the path space: (0, 0) maps to `r.min`.
+    pub fn draw_pattern(&mut self, dst: &mut RGBA, r: Rectangle, pattern: &Pattern) {
+        let inverse = match Transform::from(pattern.transform).inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let shade = |x, y| pattern.at(inverse, x, y);
+        match self.draw_op {
+            Op::Over => self.shade_over(dst, r, shade),
+            Op::Src => self.shade_src(dst, r, shade),
+        }
+    }
+}
+
+/// How a pattern continues outside of its image.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tiling {
+    /// Transparent outside of the image.
+    None,
+    /// Extends the edge pixels.
+    Clamp,
+    /// Repeats the image in both directions.
+    Repeat,
+}
+
+/// An image used as a paint.
+///
+/// The image is alpha-premultiplied and sampled with the nearest neighbour filter.
+pub struct Pattern<'a, 'b> {
+    pub image: &'a RGBA<'b>,
+    pub tiling: Tiling,
+    /// Maps image space to path space, in the same layout as `Gradient::transform`.
+    /// The identity puts `image.rect.min` at the path origin.
+    pub transform: [f32; 6],
+}
+
+impl<'a, 'b> Pattern<'a, 'b> {
+    pub fn new(image: &'a RGBA<'b>, tiling: Tiling) -> Self {
+        Self { image, tiling, transform: IDENTITY }
+    }
+
+    /// Sets the transform, as an array or a `geom::Transform`.
+    pub fn transform<M: Into<[f32; 6]>>(mut self, m: M) -> Self {
+        self.transform = m.into();
+        self
+    }
+
+    fn at(&self, inverse: Transform, x: isize, y: isize) -> [u32; 4] {
+        let [ix, iy] = inverse.apply([x as f32 + 0.5, y as f32 + 0.5]);
+        if !ix.is_finite() || !iy.is_finite() {
+            return [0; 4];
+        }
+
+        let r = self.image.rect;
+        let (w, h) = (r.dx(), r.dy());
+        if w <= 0 || h <= 0 {
+            return [0; 4];
+        }
+        let (ix, iy) = (ix.floor() as isize, iy.floor() as isize);
+        let (ix, iy) = match self.tiling {
+            Tiling::None => (ix, iy),
+            Tiling::Clamp => (ix.max(0).min(w - 1), iy.max(0).min(h - 1)),
+            Tiling::Repeat => (ix.rem_euclid(w), iy.rem_euclid(h)),
+        };
+        widen(self.image.at(r.min.x + ix, r.min.y + iy))
+    }
+}
@@ -0,0 +1,323 @@
+// This file contains a retained path that can be built once and replayed
+// into a Rasterizer many times.
+
+use super::Rasterizer;
+
+/// A path command. Points are in the same coordinates as the Rasterizer's.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Segment {
+    /// Starts a new subpath.
+    MoveTo([f32; 2]),
+    LineTo([f32; 2]),
+    /// Quadratic Bézier curve with one control point.
+    QuadTo([f32; 2], [f32; 2]),
+    /// Cubic Bézier curve with two control points.
+    CubeTo([f32; 2], [f32; 2], [f32; 2]),
+    /// Closes the current subpath with a line to its first point.
+    Close,
+}
+
+impl Segment {
+    /// Returns the point the segment ends at, or `None` for `Close`.
+    pub fn end(&self) -> Option<[f32; 2]> {
+        match *self {
+            Segment::MoveTo(p) | Segment::LineTo(p) |
+            Segment::QuadTo(_, p) | Segment::CubeTo(_, _, p) => Some(p),
+            Segment::Close => None,
+        }
+    }
+
+    fn map<F: Fn([f32; 2]) -> [f32; 2]>(self, f: F) -> Self {
+        match self {
+            Segment::MoveTo(p) => Segment::MoveTo(f(p)),
+            Segment::LineTo(p) => Segment::LineTo(f(p)),
+            Segment::QuadTo(b, c) => Segment::QuadTo(f(b), f(c)),
+            Segment::CubeTo(b, c, d) => Segment::CubeTo(f(b), f(c), f(d)),
+            Segment::Close => Segment::Close,
+        }
+    }
+}
+
+/// A sequence of subpaths made of lines and Bézier curves.
+///
+/// Every subpath starts with a `MoveTo`: drawing commands on an empty path
+/// start at (0, 0) and those after a `Close` start at the first point of the
+/// closed subpath, like the Rasterizer's pen does.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Path {
+    segments: Vec<Segment>,
+    first: [f32; 2],
+    pen: [f32; 2],
+}
+
+impl Path {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn is_empty(&self) -> bool { self.segments.is_empty() }
+
+    pub fn clear(&mut self) {
+        self.segments.clear();
+        self.first = [0.0, 0.0];
+        self.pen = [0.0, 0.0];
+    }
+
+    /// Returns the location of the pen: the end point of the last segment.
+    pub fn pen(&self) -> [f32; 2] { self.pen }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Segment> { self.segments.iter() }
+
+    pub fn move_to(&mut self, x: f32, y: f32) -> &mut Self {
+        self.push(Segment::MoveTo([x, y]))
+    }
+
+    pub fn line_to(&mut self, x: f32, y: f32) -> &mut Self {
+        self.push(Segment::LineTo([x, y]))
+    }
+
+    pub fn quad_to(&mut self, bx: f32, by: f32, cx: f32, cy: f32) -> &mut Self {
+        self.push(Segment::QuadTo([bx, by], [cx, cy]))
+    }
+
+    pub fn cube_to(&mut self, bx: f32, by: f32, cx: f32, cy: f32, dx: f32, dy: f32) -> &mut Self {
+        self.push(Segment::CubeTo([bx, by], [cx, cy], [dx, dy]))
+    }
+
+    pub fn close_path(&mut self) -> &mut Self {
+        self.push(Segment::Close)
+    }
+
+    /// Appends a segment, starting a subpath first if needed.
+    pub fn push(&mut self, seg: Segment) -> &mut Self {
+        match seg {
+            Segment::MoveTo(p) => {
+                self.first = p;
+                self.pen = p;
+            }
+            Segment::Close => {
+                if self.needs_move_to() {
+                    return self
+                }
+                self.pen = self.first;
+            }
+            _ => {
+                if self.needs_move_to() {
+                    let first = self.first;
+                    self.segments.push(Segment::MoveTo(first));
+                }
+                self.pen = seg.end().unwrap();
+            }
+        }
+        self.segments.push(seg);
+        self
+    }
+
+    fn needs_move_to(&self) -> bool {
+        matches!(self.segments.last(), None | Some(Segment::Close))
+    }
+
+    /// Appends the subpaths of `other`.
+    pub fn append(&mut self, other: &Path) -> &mut Self {
+        for &seg in other {
+            self.push(seg);
+        }
+        self
+    }
+
+    /// Applies the affine transform `m` to every point.
+    ///
+    /// The layout is `[a, b, c, d, e, f]` for `x' = a*x + c*y + e` and
+    /// `y' = b*x + d*y + f`, as for `paint::Gradient::transform`, and
+    /// `m` may also be a `geom::Transform`.
+    pub fn transform<M: Into<[f32; 6]>>(&mut self, m: M) -> &mut Self {
+        let [a, b, c, d, e, f] = m.into();
+        let t = |p: [f32; 2]| [a * p[0] + c * p[1] + e, b * p[0] + d * p[1] + f];
+        for seg in &mut self.segments {
+            *seg = seg.map(t);
+        }
+        self.first = t(self.first);
+        self.pen = t(self.pen);
+        self
+    }
+
+    /// Returns the path with every subpath running in the opposite direction.
+    ///
+    /// The subpaths keep their order and closed subpaths stay closed.
+    pub fn reverse(&self) -> Path {
+        let mut out = Path::new();
+        let mut start = 0;
+        while start < self.segments.len() {
+            let end = self.segments[start+1..].iter()
+                .position(|s| matches!(s, Segment::MoveTo(_)))
+                .map_or(self.segments.len(), |i| start + 1 + i);
+            reverse_subpath(&self.segments[start..end], &mut out);
+            start = end;
+        }
+        out
+    }
+
+    /// Returns the smallest box, as `[min, max]`, that contains every point
+    /// of the path, or `None` for an empty path.
+    ///
+    /// Curves are measured at their extremes, not at their control points.
+    pub fn bounds(&self) -> Option<[[f32; 2]; 2]> {
+        let mut bounds: Option<[[f32; 2]; 2]> = None;
+        let mut add = |p: [f32; 2]| {
+            let r = bounds.get_or_insert([p, p]);
+            for i in 0..2 {
+                if p[i] < r[0][i] { r[0][i] = p[i] }
+                if p[i] > r[1][i] { r[1][i] = p[i] }
+            }
+        };
+
+        let mut pen = [0.0, 0.0];
+        for seg in &self.segments {
+            match *seg {
+                Segment::MoveTo(p) | Segment::LineTo(p) => add(p),
+                Segment::QuadTo(b, c) => {
+                    for i in 0..2 {
+                        // The derivative is zero at t = (a - b) / (a - 2b + c).
+                        let den = pen[i] - 2.0 * b[i] + c[i];
+                        if den != 0.0 {
+                            let t = (pen[i] - b[i]) / den;
+                            if t > 0.0 && t < 1.0 {
+                                add(quad_at(pen, b, c, t));
+                            }
+                        }
+                    }
+                    add(c);
+                }
+                Segment::CubeTo(b, c, d) => {
+                    for i in 0..2 {
+                        for t in cube_extremes(pen[i], b[i], c[i], d[i]) {
+                            add(cube_at(pen, b, c, d, t));
+                        }
+                    }
+                    add(d);
+                }
+                Segment::Close => (),
+            }
+            if let Some(p) = seg.end() {
+                pen = p;
+            }
+        }
+        bounds
+    }
+}
+
+fn reverse_subpath(segs: &[Segment], out: &mut Path) {
+    let closed = segs.last() == Some(&Segment::Close);
+    let last = segs.iter().rev().find_map(|s| s.end());
+    let last = match last {
+        Some(p) => p,
+        None => return,
+    };
+    out.move_to(last[0], last[1]);
+
+    // Each segment is walked backwards from its end point to the point
+    // before it.
+    let mut starts = Vec::with_capacity(segs.len());
+    let mut pen = [0.0, 0.0];
+    for seg in segs {
+        starts.push(pen);
+        if let Some(p) = seg.end() {
+            pen = p;
+        }
+    }
+    for (seg, &p) in segs.iter().zip(&starts).rev() {
+        match *seg {
+            Segment::LineTo(_) => { out.line_to(p[0], p[1]); }
+            Segment::QuadTo(b, _) => { out.quad_to(b[0], b[1], p[0], p[1]); }
+            Segment::CubeTo(b, c, _) => { out.cube_to(c[0], c[1], b[0], b[1], p[0], p[1]); }
+            Segment::MoveTo(_) | Segment::Close => (),
+        }
+    }
+    if closed {
+        out.close_path();
+    }
+}
+
+fn quad_at(a: [f32; 2], b: [f32; 2], c: [f32; 2], t: f32) -> [f32; 2] {
+    let u = 1.0 - t;
+    let f = |i: usize| u * u * a[i] + 2.0 * u * t * b[i] + t * t * c[i];
+    [f(0), f(1)]
+}
+
+fn cube_at(a: [f32; 2], b: [f32; 2], c: [f32; 2], d: [f32; 2], t: f32) -> [f32; 2] {
+    let u = 1.0 - t;
+    let f = |i: usize| u * u * u * a[i] + 3.0 * u * u * t * b[i] + 3.0 * u * t * t * c[i] + t * t * t * d[i];
+    [f(0), f(1)]
+}
+
+// cube_extremes returns the parameters in (0, 1) where the derivative of a
+// one-dimensional cubic Bézier curve is zero.
+fn cube_extremes(a: f32, b: f32, c: f32, d: f32) -> Vec<f32> {
+    // The derivative is 3 times the quadratic p*t² + q*t + r.
+    let p = -a + 3.0 * b - 3.0 * c + d;
+    let q = 2.0 * (a - 2.0 * b + c);
+    let r = b - a;
+
+    let mut ts = Vec::with_capacity(2);
+    if p.abs() < 1e-12 {
+        if q != 0.0 {
+            ts.push(-r / q);
+        }
+    } else {
+        let disc = q * q - 4.0 * p * r;
+        if disc >= 0.0 {
+            let sq = disc.sqrt();
+            ts.push((-q + sq) / (2.0 * p));
+            ts.push((-q - sq) / (2.0 * p));
+        }
+    }
+    ts.retain(|&t| t > 0.0 && t < 1.0);
+    ts
+}
+
+impl<'a> IntoIterator for &'a Path {
+    type Item = &'a Segment;
+    type IntoIter = std::slice::Iter<'a, Segment>;
+    fn into_iter(self) -> Self::IntoIter { self.segments.iter() }
+}
+
+impl std::iter::FromIterator<Segment> for Path {
+    fn from_iter<I: IntoIterator<Item = Segment>>(iter: I) -> Self {
+        let mut path = Path::new();
+        path.extend(iter);
+        path
+    }
+}
+
+impl Extend<Segment> for Path {
+    fn extend<I: IntoIterator<Item = Segment>>(&mut self, iter: I) {
+        for seg in iter {
+            self.push(seg);
+        }
+    }
+}
+
+impl Rasterizer {
+    /// Adds the segments of `path`, as if by the matching XxxTo calls.
+    ///
+    /// Open subpaths are closed, as filling requires.
+    pub fn add_path(&mut self, path: &Path) {
+        let mut open = false;
+        for seg in path {
+            match *seg {
+                Segment::MoveTo([x, y]) => {
+                    if open {
+                        self.close_path();
+                    }
+                    self.move_to(x, y);
+                }
+                Segment::LineTo([x, y]) => self.line_to(x, y),
+                Segment::QuadTo([bx, by], [cx, cy]) => self.quad_to(bx, by, cx, cy),
+                Segment::CubeTo([bx, by], [cx, cy], [dx, dy]) => self.cube_to(bx, by, cx, cy, dx, dy),
+                Segment::Close => self.close_path(),
+            }
+            open = *seg != Segment::Close;
+        }
+        if open {
+            self.close_path();
+        }
+    }
+}
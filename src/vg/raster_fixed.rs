@@ -1,20 +1,20 @@
 // This file contains a fixed point math implementation of the vector
 // graphics rasterizer.
 
-use super::{clamp, Rasterizer};
+use super::{clamp, Rasterizer, FillRule};
 
 // ϕ is the number of binary digits after the fixed point.
 //
 // For example, if ϕ == 10 (and int1ϕ is based on the int32 type) then we
 // are using 22.10 fixed point math.
 //
-// When changing this number, also change the assembly code (search for ϕ
-// in the .s files).
-const ϕ: int1ϕ = 9;
+// When changing this number, also check the shifts in the SIMD code (search
+// for ϕ in simd.rs).
+pub(super) const ϕ: int1ϕ = 9;
 
 const fxOne:          int1ϕ = 1 << ϕ;
-const fxOneAndAHalf:  int1ϕ = 1<<ϕ + 1<<(ϕ-1);
-const fxOneMinusIota: int1ϕ = 1<<ϕ - 1; // Used for rounding up.
+const fxOneAndAHalf:  int1ϕ = (1<<ϕ) + (1<<(ϕ-1));
+const fxOneMinusIota: int1ϕ = (1<<ϕ) - 1; // Used for rounding up.
 
 // int1ϕ is a signed fixed-point number with 1*ϕ binary digits after the fixed
 // point.
@@ -34,20 +34,32 @@ type int2ϕ = i32;
 #[inline(always)] fn fmax(x: int1ϕ, y: int1ϕ) -> int1ϕ { if x > y { x } else { y } }
 #[inline(always)] fn fmin(x: int1ϕ, y: int1ϕ) -> int1ϕ { if x < y { x } else { y } }
 
+// coverage returns the absolute coverage of the accumulated area, in int2ϕ
+// units, before clamping to one.
+#[inline(always)]
+fn coverage(acc: int2ϕ, rule: FillRule) -> int2ϕ {
+    let a = acc.wrapping_abs();
+    match rule {
+        FillRule::NonZero => a,
+        FillRule::EvenOdd => {
+            const ONE: int2ϕ = 1 << (2*ϕ);
+            let a = a & (2*ONE - 1);
+            if a > ONE { 2*ONE - a } else { a }
+        }
+    }
+}
+
 #[inline(always)] fn floor(x: int1ϕ) -> i32 { (x >> ϕ) }
 #[inline(always)] fn ceil(x: int1ϕ) -> i32  { ((x + fxOneMinusIota) >> ϕ) }
 
 impl Rasterizer {
     pub fn fixed_accumulate_mask(&mut self) {
+        if self.accumulated { return }
+        self.accumulated = true;
+        let (spans, rule) = (self.dirty_spans(), self.fill_rule);
         let buf = self.buf.as_u32();
-        let mut acc = 0i32;
-        for v in buf {
-            acc += (*v) as i32;
-            let mut a = acc;
-            if a < 0 { a = -a }
-            a >>= 2*ϕ - 16;
-            if a > 0xffff { a = 0xffff; }
-            *v = a as u32;
+        for (_, span) in spans {
+            accumulate_mask(&mut buf[span], rule)
         }
     }
 
@@ -86,9 +98,9 @@ impl Rasterizer {
             let x_next = x + ((dy as f32)*dxdy) as int1ϕ;
             if y < 0 {
                 x = x_next;
+                y += 1;
                 continue;
             }
-            let buf = &mut self.buf.as_u32()[(y*width) as usize..];
             let d = dy * dir; // d ranges up to ±1<<(1*ϕ).
             let (x0, x1) = if x > x_next {
                 (x_next, x)
@@ -99,16 +111,17 @@ impl Rasterizer {
             let x0floor = (x0i as int1ϕ) << ϕ;
             let x1i = ceil(x1);
             let x1ceil = (x1i as int1ϕ) << ϕ;
+            let mut buf = self.row_u32(y, x0i, x1i.max(x0i+1));
 
             if x1i <= x0i+1 {
-                let xmf = (x+x_next)>>1 - x0floor;
+                let xmf = ((x+x_next)>>1) - x0floor;
                 let i = clamp(x0i+0, width);
                 if i < buf.len() {
-                    buf[i] += (d * (fxOne - xmf)) as u32
+                    buf[i] = buf[i].wrapping_add((d * (fxOne - xmf)) as u32);
                 }
                 let i = clamp(x0i+1, width);
                 if i < buf.len() {
-                    buf[i] += (d * xmf) as u32
+                    buf[i] = buf[i].wrapping_add((d * xmf) as u32);
                 }
             } else {
                 let one_over_s = x1 - x0;
@@ -134,7 +147,7 @@ impl Rasterizer {
                     let mut D = one_minus_x0f_squared; // D ranges up to ±1<<(2*ϕ).
                     D *= d;                            // D ranges up to ±1<<(3*ϕ).
                     D /= two_over_s;
-                    buf[i] += D as u32;
+                    buf[i] = buf[i].wrapping_add(D as u32);
                 }
 
                 if x1i == x0i+2 {
@@ -144,10 +157,10 @@ impl Rasterizer {
                         //
                         // (x1i == x0i+2) and (twoOverS == 2 * (x1 - x0)) implies
                         // that twoOverS ranges up to +1<<(1*ϕ+2).
-                        let mut D = two_over_s<<ϕ - one_minus_x0f_squared - x1f_squared; // D ranges up to ±1<<(2*ϕ+2).
+                        let mut D = (two_over_s<<ϕ) - one_minus_x0f_squared - x1f_squared; // D ranges up to ±1<<(2*ϕ+2).
                         D *= d;                                            // D ranges up to ±1<<(3*ϕ+2).
                         D /= two_over_s;
-                        buf[i] += D as u32;
+                        buf[i] = buf[i].wrapping_add(D as u32);
                     }
                 } else {
                     // This is commented out for the same reason as a0 and am.
@@ -188,16 +201,16 @@ impl Rasterizer {
                         // Thus, A ranges up to ±1<<(2*ϕ+2). It is possible to
                         // derive a tighter bound, but this bound is sufficient to
                         // reason about overflow.
-                        let mut D = (fxOneAndAHalf-x0f)<<(ϕ+1) - one_minus_x0f_squared; // D ranges up to ±1<<(2*ϕ+2).
+                        let mut D = ((fxOneAndAHalf-x0f)<<(ϕ+1)) - one_minus_x0f_squared; // D ranges up to ±1<<(2*ϕ+2).
                         D *= d;                                               // D ranges up to ±1<<(3*ϕ+2).
                         D /= two_over_s;
-                        buf[i] += D as u32;
+                        buf[i] = buf[i].wrapping_add(D as u32);
                     }
                     let d_times_s = ((d << (2 * ϕ)) / one_over_s) as u32;
                     for xi in (x0i + 2)..(x1i-1) {
                         let i = clamp(xi, width);
                         if  i < buf.len() {
-                            buf[i] += d_times_s;
+                            buf[i] = buf[i].wrapping_add(d_times_s);
                         }
                     }
 
@@ -248,13 +261,12 @@ impl Rasterizer {
                         // to ±1<<(ϕ+2). One final simplification:
                         //	B = x1f<<1 + (1<<(ϕ+2) - fxOneAndAHalf<<1)
                         //const C: i32 = 1<<(ϕ+2) - fxOneAndAHalf<<1;
-                        #[allow(exceeding_bitshifts)]
-                        let mut D = x1f<<1 + (1<<(ϕ+2) - fxOneAndAHalf<<1); // D ranges up to ±1<<(1*ϕ+2).
+                        let mut D = (x1f<<1) + ((1<<(ϕ+2)) - (fxOneAndAHalf<<1)); // D ranges up to ±1<<(1*ϕ+2).
                         D <<= ϕ;          // D ranges up to ±1<<(2*ϕ+2).
                         D -= x1f_squared; // D ranges up to ±1<<(2*ϕ+3).
                         D *= d;           // D ranges up to ±1<<(3*ϕ+3).
                         D /= two_over_s;
-                        buf[i] += D as u32;
+                        buf[i] = buf[i].wrapping_add(D as u32);
                     }
                 }
                 let i = clamp(x1i, width);
@@ -263,7 +275,7 @@ impl Rasterizer {
                     let mut D = x1f_squared; // D ranges up to ±1<<(2*ϕ).
                     D *= d;         // D ranges up to ±1<<(3*ϕ).
                     D /= two_over_s;
-                    buf[i] += D as u32;
+                    buf[i] = buf[i].wrapping_add(D as u32);
                 }
             }
 
@@ -273,51 +285,43 @@ impl Rasterizer {
     }
 }
 
-
-/*
-fn fixedAccumulateOpOver(dst []uint8, src []uint32) {
-    // Sanity check that len(dst) >= len(src).
-    if len(dst) < len(src) {
-        return
+pub fn accumulate_mask(buf: &mut [u32], rule: FillRule) {
+    let mut acc: int2ϕ = 0;
+    for v in buf {
+        acc = acc.wrapping_add(*v as int2ϕ);
+        let mut a = coverage(acc, rule);
+        a >>= 2*ϕ - 16;
+        if a > 0xffff { a = 0xffff; }
+        *v = a as u32;
     }
+}
 
-    acc := int2ϕ(0)
-    for i, v := range src {
-        acc += int2ϕ(v)
-        a := acc
-        if a < 0 {
-            a = -a
-        }
-        a >>= 2*ϕ - 16
-        if a > 0xffff {
-            a = 0xffff
-        }
+pub fn accumulate_op_over(dst: &mut [u8], src: &[u32], rule: FillRule) {
+    // Sanity check that dst.len() >= src.len().
+    if dst.len() < src.len() { return }
+    let mut acc: int2ϕ = 0;
+    for (i, v) in src.iter().enumerate() {
+        acc = acc.wrapping_add(*v as int2ϕ);
+        let mut a = coverage(acc, rule);
+        a >>= 2*ϕ - 16;
+        if a > 0xffff { a = 0xffff }
         // This algorithm comes from the standard library's image/draw package.
-        dstA := uint32(dst[i]) * 0x101
-        maskA := uint32(a)
-        outA := dstA*(0xffff-maskA)/0xffff + maskA
-        dst[i] = uint8(outA >> 8)
+        let dst_a = (dst[i] as u32) * 0x101;
+        let mask_a = a as u32;
+        let out_a = dst_a * (0xffff - mask_a) / 0xffff + mask_a;
+        dst[i] = (out_a >> 8) as u8;
     }
 }
 
-fn fixedAccumulateOpSrc(dst []uint8, src []uint32) {
-    // Sanity check that len(dst) >= len(src).
-    if len(dst) < len(src) {
-        return
-    }
-
-    acc := int2ϕ(0)
-    for i, v := range src {
-        acc += int2ϕ(v)
-        a := acc
-        if a < 0 {
-            a = -a
-        }
-        a >>= 2*ϕ - 8
-        if a > 0xff {
-            a = 0xff
-        }
-        dst[i] = uint8(a)
+pub fn accumulate_op_src(dst: &mut [u8], src: &[u32], rule: FillRule) {
+    // Sanity check that dst.len() >= src.len().
+    if dst.len() < src.len() { return }
+    let mut acc: int2ϕ = 0;
+    for (i, v) in src.iter().enumerate() {
+        acc = acc.wrapping_add(*v as int2ϕ);
+        let mut a = coverage(acc, rule);
+        a >>= 2*ϕ - 8;
+        if a > 0xff { a = 0xff }
+        dst[i] = a as u8;
     }
 }
-*/
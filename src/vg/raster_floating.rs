@@ -1,6 +1,6 @@
 /// This file contains a floating point math implementation of the vector graphics rasterizer.
 
-use super::{clamp, Rasterizer};
+use super::{clamp, Rasterizer, FillRule};
 
 #[inline(always)] fn fmax(x: f32, y: f32) -> f32 { if x > y { x } else { y } }
 #[inline(always)] fn fmin(x: f32, y: f32) -> f32 { if x < y { x } else { y } }
@@ -9,12 +9,12 @@ use super::{clamp, Rasterizer};
 
 impl Rasterizer {
     pub fn floating_accumulate_mask(&mut self) {
+        if self.accumulated { return }
+        self.accumulated = true;
+        let (spans, rule) = (self.dirty_spans(), self.fill_rule);
         let buf = self.buf.as_u32();
-        let mut acc = 0f32;
-        for v in buf {
-            acc += unsafe { *(v as *mut u32 as *mut f32) };
-            let a = clamp_alpha(acc);
-            *v = (ALMOST65536 * a) as u32;
+        for (_, span) in spans {
+            accumulate_mask_x(&mut buf[span], rule)
         }
     }
 
@@ -62,10 +62,10 @@ impl Rasterizer {
             let x_next = x + (dy * dxdy) as f32;
             if y < 0 {
                 x = x_next;
+                y += 1;
                 continue;
             }
 
-            let buf = &mut self.buf.as_f32()[(y*width) as usize..];
             let d = (dy * dir) as f32;
 
             let (x0, x1) = if x > x_next {
@@ -79,6 +79,7 @@ impl Rasterizer {
             let x0floor = x0i as f32;
             let x1i = ceil(x1);
             let x1ceil = x1i as f32;
+            let mut buf = self.row_f32(y, x0i, x1i.max(x0i+1));
 
             if x1i <= x0i+1 {
                 let xmf = (0.5 * (x+x_next)) as f32 - x0floor;
@@ -155,28 +156,36 @@ impl Rasterizer {
 // instead of the maximal value 0xff.
 //
 // math.Float32bits(almost256) is 0x437fffff.
-const ALMOST256: f32 = 255.99998;
+pub(super) const ALMOST256: f32 = 255.99998;
 
 // almost65536 scales a floating point value in the range [0, 1] to a
 // uint16 value in the range [0x0000, 0xffff].
 //
 // math.Float32bits(almost65536) is 0x477fffff.
-const ALMOST65536: f32 = ALMOST256 * 256.0;
+pub(super) const ALMOST65536: f32 = ALMOST256 * 256.0;
 
 #[inline(always)]
-fn clamp_alpha(mut a: f32) -> f32 {
+fn clamp_alpha(mut a: f32, rule: FillRule) -> f32 {
     if a < 0.0 { a = -a; }
-    if a > 1.0 { a = 1.0; }
+    match rule {
+        FillRule::NonZero => {
+            if a > 1.0 { a = 1.0; }
+        }
+        FillRule::EvenOdd => {
+            a %= 2.0;
+            if a > 1.0 { a = 2.0 - a; }
+        }
+    }
     a
 }
 
-pub fn accumulate_op_over(dst: &mut [u8], src: &[f32]) {
+pub fn accumulate_op_over(dst: &mut [u8], src: &[f32], rule: FillRule) {
     // Sanity check that dst.len() >= src.len().
     if dst.len() < src.len() { return }
     let mut acc = 0f32;
     for (i, v) in src.iter().enumerate() {
         acc += *v;
-        let a = clamp_alpha(acc);
+        let a = clamp_alpha(acc, rule);
         // This algorithm comes from the standard library's image/draw package.
         let dst_a = (dst[i] as u32) * 0x101;
         let mask_a = (ALMOST65536 * a) as u32;
@@ -185,37 +194,37 @@ pub fn accumulate_op_over(dst: &mut [u8], src: &[f32]) {
     }
 }
 
-pub fn accumulate_op_src(dst: &mut [u8], src: &[f32]) {
+pub fn accumulate_op_src(dst: &mut [u8], src: &[f32], rule: FillRule) {
     // Sanity check that dst.len() >= src.len().
     if dst.len() < src.len() { return }
     let mut acc = 0f32;
     for (i, v) in src.iter().enumerate() {
         acc += *v;
-        let a = clamp_alpha(acc);
+        let a = clamp_alpha(acc, rule);
         dst[i] = (ALMOST256 * a) as u8;
     }
 }
 
-pub fn accumulate_mask(dst: &mut [u32], src: &[f32]) {
+pub fn accumulate_mask(dst: &mut [u32], src: &[f32], rule: FillRule) {
     // Sanity check that dst.len() >= src.len().
     if dst.len() < src.len() { return }
     let mut acc = 0f32;
     for (i, v) in src.iter().enumerate() {
         acc += *v;
-        let a = clamp_alpha(acc);
+        let a = clamp_alpha(acc, rule);
         dst[i] = (ALMOST65536 * a) as u32;
     }
 }
 
-pub fn accumulate_mask_x(buf: &mut [u32]) {
+pub fn accumulate_mask_x(buf: &mut [u32], rule: FillRule) {
     let src = unsafe { std::mem::transmute(&buf[..]) };
-    accumulate_mask(buf, src)
+    accumulate_mask(buf, src, rule)
 }
 
-pub fn accumulate_mask_inplace(buf: &mut super::SimdVec) {
+pub fn accumulate_mask_inplace(buf: &mut super::SimdVec, rule: FillRule) {
     unsafe {
         let dst = buf.u_u32();
         let src = buf.u_f32();
-        accumulate_mask(dst, src)
+        accumulate_mask(dst, src, rule)
     }
 }
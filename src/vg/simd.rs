@@ -0,0 +1,608 @@
+// This file contains SSE2 and AVX2 implementations of the accumulation step,
+// the integration of the individual area values to the cumulative ones.
+//
+// Like the scalar versions in raster_fixed.rs and raster_floating.rs, the
+// kernels compute a running sum of the cells, four or eight at a time, and
+// convert it to coverage. The instruction set is picked at run time, falling
+// back to the scalar code on other CPUs and architectures.
+//
+// The results are identical to the scalar ones. The fixed point sums wrap the
+// same way whatever the order, but floating point addition is not associative,
+// so the floating point sums are added one lane after the other, as in the
+// scalar loop, and only the coverage is computed in parallel.
+
+use super::{raster_fixed, raster_floating, FillRule};
+
+pub fn fixed_accumulate_mask(buf: &mut [u32], rule: FillRule) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { avx2::fixed_accumulate_mask(buf, rule) }
+        }
+        if is_x86_feature_detected!("sse2") {
+            return unsafe { sse2::fixed_accumulate_mask(buf, rule) }
+        }
+    }
+    raster_fixed::accumulate_mask(buf, rule)
+}
+
+pub fn fixed_accumulate_op_over(dst: &mut [u8], src: &[u32], rule: FillRule) {
+    // Sanity check that dst.len() >= src.len().
+    if dst.len() < src.len() { return }
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { avx2::fixed_accumulate_op_over(dst, src, rule) }
+        }
+        if is_x86_feature_detected!("sse2") {
+            return unsafe { sse2::fixed_accumulate_op_over(dst, src, rule) }
+        }
+    }
+    raster_fixed::accumulate_op_over(dst, src, rule)
+}
+
+pub fn fixed_accumulate_op_src(dst: &mut [u8], src: &[u32], rule: FillRule) {
+    // Sanity check that dst.len() >= src.len().
+    if dst.len() < src.len() { return }
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { avx2::fixed_accumulate_op_src(dst, src, rule) }
+        }
+        if is_x86_feature_detected!("sse2") {
+            return unsafe { sse2::fixed_accumulate_op_src(dst, src, rule) }
+        }
+    }
+    raster_fixed::accumulate_op_src(dst, src, rule)
+}
+
+/// Accumulates the f32 cells of `buf` in place, leaving 16-bit coverage values.
+pub fn floating_accumulate_mask(buf: &mut [u32], rule: FillRule) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { avx2::floating_accumulate_mask(buf, rule) }
+        }
+        if is_x86_feature_detected!("sse2") {
+            return unsafe { sse2::floating_accumulate_mask(buf, rule) }
+        }
+    }
+    raster_floating::accumulate_mask_x(buf, rule)
+}
+
+pub fn floating_accumulate_op_over(dst: &mut [u8], src: &[f32], rule: FillRule) {
+    // Sanity check that dst.len() >= src.len().
+    if dst.len() < src.len() { return }
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { avx2::floating_accumulate_op_over(dst, src, rule) }
+        }
+        if is_x86_feature_detected!("sse2") {
+            return unsafe { sse2::floating_accumulate_op_over(dst, src, rule) }
+        }
+    }
+    raster_floating::accumulate_op_over(dst, src, rule)
+}
+
+pub fn floating_accumulate_op_src(dst: &mut [u8], src: &[f32], rule: FillRule) {
+    // Sanity check that dst.len() >= src.len().
+    if dst.len() < src.len() { return }
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { avx2::floating_accumulate_op_src(dst, src, rule) }
+        }
+        if is_x86_feature_detected!("sse2") {
+            return unsafe { sse2::floating_accumulate_op_src(dst, src, rule) }
+        }
+    }
+    raster_floating::accumulate_op_src(dst, src, rule)
+}
+
+// chunks! runs $step on every $n cells, with $d and $s pointing to the
+// destination and the source. The last partial chunk goes through zero padded
+// copies, since zero cells leave the running sum unchanged.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+macro_rules! chunks {
+    ($n:expr, $buf:ident: $T:ty, |$d:ident| $step:expr) => {{
+        let len = $buf.len();
+        let mut i = 0;
+        while i + $n <= len {
+            let $d = $buf.as_mut_ptr().add(i);
+            $step;
+            i += $n;
+        }
+        if i < len {
+            let mut tmp = [0 as $T; $n];
+            tmp[..len - i].copy_from_slice(&$buf[i..]);
+            let $d = tmp.as_mut_ptr();
+            $step;
+            $buf[i..].copy_from_slice(&tmp[..len - i]);
+        }
+    }};
+    ($n:expr, $dst:ident: $D:ty, $src:ident: $S:ty, |$d:ident, $s:ident| $step:expr) => {{
+        let len = $src.len();
+        let mut i = 0;
+        while i + $n <= len {
+            let ($d, $s) = ($dst.as_mut_ptr().add(i), $src.as_ptr().add(i));
+            $step;
+            i += $n;
+        }
+        if i < len {
+            let (mut d, mut s) = ([0 as $D; $n], [0 as $S; $n]);
+            d[..len - i].copy_from_slice(&$dst[i..len]);
+            s[..len - i].copy_from_slice(&$src[i..]);
+            let ($d, $s) = (d.as_mut_ptr(), s.as_ptr());
+            $step;
+            $dst[i..len].copy_from_slice(&d[..len - i]);
+        }
+    }};
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod sse2 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::super::FillRule;
+    use super::super::raster_fixed::ϕ;
+    use super::super::raster_floating::{ALMOST256, ALMOST65536};
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn fixed_accumulate_mask(buf: &mut [u32], rule: FillRule) {
+        let mut offset = _mm_setzero_si128();
+        chunks!(4, buf: u32, |d| {
+            let p = d as *mut __m128i;
+            let a = fixed_coverage(fixed_sum(&mut offset, _mm_loadu_si128(p)), rule);
+            let a = min(_mm_srai_epi32(a, 2*ϕ - 16), 0xffff);
+            _mm_storeu_si128(p, a);
+        })
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn fixed_accumulate_op_over(dst: &mut [u8], src: &[u32], rule: FillRule) {
+        let mut offset = _mm_setzero_si128();
+        chunks!(4, dst: u8, src: u32, |d, s| {
+            let x = _mm_loadu_si128(s as *const __m128i);
+            let a = fixed_coverage(fixed_sum(&mut offset, x), rule);
+            let a = min(_mm_srai_epi32(a, 2*ϕ - 16), 0xffff);
+            store_u8(d, over(d, a));
+        })
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn fixed_accumulate_op_src(dst: &mut [u8], src: &[u32], rule: FillRule) {
+        let mut offset = _mm_setzero_si128();
+        chunks!(4, dst: u8, src: u32, |d, s| {
+            let x = _mm_loadu_si128(s as *const __m128i);
+            let a = fixed_coverage(fixed_sum(&mut offset, x), rule);
+            store_u8(d, min(_mm_srai_epi32(a, 2*ϕ - 8), 0xff));
+        })
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn floating_accumulate_mask(buf: &mut [u32], rule: FillRule) {
+        let mut offset = 0.0;
+        chunks!(4, buf: u32, |d| {
+            let x = _mm_loadu_ps(d as *const f32);
+            let a = floating_coverage(floating_sum(&mut offset, x), rule);
+            let a = _mm_cvttps_epi32(_mm_mul_ps(a, _mm_set1_ps(ALMOST65536)));
+            _mm_storeu_si128(d as *mut __m128i, a);
+        })
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn floating_accumulate_op_over(dst: &mut [u8], src: &[f32], rule: FillRule) {
+        let mut offset = 0.0;
+        chunks!(4, dst: u8, src: f32, |d, s| {
+            let a = floating_coverage(floating_sum(&mut offset, _mm_loadu_ps(s)), rule);
+            let a = _mm_cvttps_epi32(_mm_mul_ps(a, _mm_set1_ps(ALMOST65536)));
+            store_u8(d, over(d, a));
+        })
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn floating_accumulate_op_src(dst: &mut [u8], src: &[f32], rule: FillRule) {
+        let mut offset = 0.0;
+        chunks!(4, dst: u8, src: f32, |d, s| {
+            let a = floating_coverage(floating_sum(&mut offset, _mm_loadu_ps(s)), rule);
+            store_u8(d, _mm_cvttps_epi32(_mm_mul_ps(a, _mm_set1_ps(ALMOST256))));
+        })
+    }
+
+    // fixed_sum returns the running sums of x's lanes, starting from offset,
+    // and sets offset to the last one.
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    unsafe fn fixed_sum(offset: &mut __m128i, x: __m128i) -> __m128i {
+        let x = _mm_add_epi32(x, _mm_slli_si128(x, 4));
+        let x = _mm_add_epi32(x, _mm_slli_si128(x, 8));
+        let x = _mm_add_epi32(x, *offset);
+        *offset = _mm_shuffle_epi32(x, 0xff);
+        x
+    }
+
+    // floating_sum is fixed_sum for floats. The lanes are added in order,
+    // starting from offset, to round like the scalar code.
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    unsafe fn floating_sum(offset: &mut f32, x: __m128) -> __m128 {
+        let mut v = [0f32; 4];
+        _mm_storeu_ps(v.as_mut_ptr(), x);
+        for v in &mut v {
+            *offset += *v;
+            *v = *offset;
+        }
+        _mm_loadu_ps(v.as_ptr())
+    }
+
+    // fixed_coverage is raster_fixed's coverage, four lanes at a time.
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    unsafe fn fixed_coverage(acc: __m128i, rule: FillRule) -> __m128i {
+        // SSE2 has no _mm_abs_epi32.
+        let sign = _mm_srai_epi32(acc, 31);
+        let a = _mm_sub_epi32(_mm_xor_si128(acc, sign), sign);
+        match rule {
+            FillRule::NonZero => a,
+            FillRule::EvenOdd => {
+                let one = _mm_set1_epi32(1 << (2*ϕ));
+                let two = _mm_set1_epi32(2 << (2*ϕ));
+                let a = _mm_and_si128(a, _mm_sub_epi32(two, _mm_set1_epi32(1)));
+                select(_mm_cmpgt_epi32(a, one), _mm_sub_epi32(two, a), a)
+            }
+        }
+    }
+
+    // floating_coverage is raster_floating's clamp_alpha, four lanes at a time.
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    unsafe fn floating_coverage(acc: __m128, rule: FillRule) -> __m128 {
+        let one = _mm_set1_ps(1.0);
+        let a = _mm_andnot_ps(_mm_set1_ps(-0.0), acc);
+        match rule {
+            FillRule::NonZero => _mm_min_ps(a, one),
+            FillRule::EvenOdd => {
+                // a % 2 is exactly a - 2*trunc(a/2), and truncation is the
+                // floor for non-negative values.
+                let two = _mm_set1_ps(2.0);
+                let k = _mm_cvtepi32_ps(_mm_cvttps_epi32(_mm_mul_ps(a, _mm_set1_ps(0.5))));
+                let a = _mm_sub_ps(a, _mm_mul_ps(k, two));
+                let m = _mm_cmpgt_ps(a, one);
+                _mm_or_ps(_mm_and_ps(m, _mm_sub_ps(two, a)), _mm_andnot_ps(m, a))
+            }
+        }
+    }
+
+    // over returns the four dst bytes at d with an opaque source composited
+    // over them using the 16-bit coverage ma, as in the scalar code:
+    //	out_a = dst_a * (0xffff - ma) / 0xffff + ma
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    unsafe fn over(d: *const u8, ma: __m128i) -> __m128i {
+        let zero = _mm_setzero_si128();
+        let x = _mm_cvtsi32_si128((d as *const i32).read_unaligned());
+        let x = _mm_unpacklo_epi16(_mm_unpacklo_epi8(x, zero), zero);
+        let dst_a = _mm_or_si128(x, _mm_slli_epi32(x, 8));
+        let y = _mm_sub_epi32(_mm_set1_epi32(0xffff), ma);
+
+        // Both factors fit in 16 bits, so the 32-bit product is pieced together
+        // from 16-bit multiplications, which SSE2 has.
+        let lo = _mm_mullo_epi16(dst_a, y);
+        let hi = _mm_mulhi_epu16(dst_a, y);
+        let n = _mm_or_si128(lo, _mm_slli_epi32(hi, 16));
+
+        // n / 0xffff is (n + n>>16 + 1) >> 16 for every n = dst_a * y.
+        let q = _mm_add_epi32(n, _mm_srli_epi32(n, 16));
+        let q = _mm_srli_epi32(_mm_add_epi32(q, _mm_set1_epi32(1)), 16);
+        _mm_srli_epi32(_mm_add_epi32(q, ma), 8)
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    unsafe fn min(a: __m128i, max: i32) -> __m128i {
+        let max = _mm_set1_epi32(max);
+        select(_mm_cmpgt_epi32(a, max), max, a)
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    unsafe fn select(mask: __m128i, a: __m128i, b: __m128i) -> __m128i {
+        _mm_or_si128(_mm_and_si128(mask, a), _mm_andnot_si128(mask, b))
+    }
+
+    // store_u8 writes the low bytes of a's lanes to d, like an `as u8` cast.
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    unsafe fn store_u8(d: *mut u8, a: __m128i) {
+        let a = _mm_and_si128(a, _mm_set1_epi32(0xff));
+        let a = _mm_packus_epi16(_mm_packs_epi32(a, a), a);
+        (d as *mut i32).write_unaligned(_mm_cvtsi128_si32(a));
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod avx2 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::super::FillRule;
+    use super::super::raster_fixed::ϕ;
+    use super::super::raster_floating::{ALMOST256, ALMOST65536};
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn fixed_accumulate_mask(buf: &mut [u32], rule: FillRule) {
+        let mut offset = _mm256_setzero_si256();
+        chunks!(8, buf: u32, |d| {
+            let p = d as *mut __m256i;
+            let a = fixed_coverage(fixed_sum(&mut offset, _mm256_loadu_si256(p)), rule);
+            let a = _mm256_min_epi32(_mm256_srai_epi32(a, 2*ϕ - 16), _mm256_set1_epi32(0xffff));
+            _mm256_storeu_si256(p, a);
+        })
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn fixed_accumulate_op_over(dst: &mut [u8], src: &[u32], rule: FillRule) {
+        let mut offset = _mm256_setzero_si256();
+        chunks!(8, dst: u8, src: u32, |d, s| {
+            let x = _mm256_loadu_si256(s as *const __m256i);
+            let a = fixed_coverage(fixed_sum(&mut offset, x), rule);
+            let a = _mm256_min_epi32(_mm256_srai_epi32(a, 2*ϕ - 16), _mm256_set1_epi32(0xffff));
+            store_u8(d, over(d, a));
+        })
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn fixed_accumulate_op_src(dst: &mut [u8], src: &[u32], rule: FillRule) {
+        let mut offset = _mm256_setzero_si256();
+        chunks!(8, dst: u8, src: u32, |d, s| {
+            let x = _mm256_loadu_si256(s as *const __m256i);
+            let a = fixed_coverage(fixed_sum(&mut offset, x), rule);
+            store_u8(d, _mm256_min_epi32(_mm256_srai_epi32(a, 2*ϕ - 8), _mm256_set1_epi32(0xff)));
+        })
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn floating_accumulate_mask(buf: &mut [u32], rule: FillRule) {
+        let mut offset = 0.0;
+        chunks!(8, buf: u32, |d| {
+            let x = _mm256_loadu_ps(d as *const f32);
+            let a = floating_coverage(floating_sum(&mut offset, x), rule);
+            let a = _mm256_cvttps_epi32(_mm256_mul_ps(a, _mm256_set1_ps(ALMOST65536)));
+            _mm256_storeu_si256(d as *mut __m256i, a);
+        })
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn floating_accumulate_op_over(dst: &mut [u8], src: &[f32], rule: FillRule) {
+        let mut offset = 0.0;
+        chunks!(8, dst: u8, src: f32, |d, s| {
+            let a = floating_coverage(floating_sum(&mut offset, _mm256_loadu_ps(s)), rule);
+            let a = _mm256_cvttps_epi32(_mm256_mul_ps(a, _mm256_set1_ps(ALMOST65536)));
+            store_u8(d, over(d, a));
+        })
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn floating_accumulate_op_src(dst: &mut [u8], src: &[f32], rule: FillRule) {
+        let mut offset = 0.0;
+        chunks!(8, dst: u8, src: f32, |d, s| {
+            let a = floating_coverage(floating_sum(&mut offset, _mm256_loadu_ps(s)), rule);
+            store_u8(d, _mm256_cvttps_epi32(_mm256_mul_ps(a, _mm256_set1_ps(ALMOST256))));
+        })
+    }
+
+    // fixed_sum returns the running sums of x's lanes, starting from offset,
+    // and sets offset to the last one.
+    //
+    // The byte shifts only work within each 128-bit half, so the total of the
+    // low half is then added to the high half.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn fixed_sum(offset: &mut __m256i, x: __m256i) -> __m256i {
+        let x = _mm256_add_epi32(x, _mm256_slli_si256(x, 4));
+        let x = _mm256_add_epi32(x, _mm256_slli_si256(x, 8));
+        let t = _mm256_shuffle_epi32(x, 0xff);
+        let x = _mm256_add_epi32(x, _mm256_permute2x128_si256(t, t, 0x08));
+        let x = _mm256_add_epi32(x, *offset);
+        *offset = _mm256_permutevar8x32_epi32(x, _mm256_set1_epi32(7));
+        x
+    }
+
+    // floating_sum is the SSE2 floating_sum, eight lanes at a time.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn floating_sum(offset: &mut f32, x: __m256) -> __m256 {
+        let mut v = [0f32; 8];
+        _mm256_storeu_ps(v.as_mut_ptr(), x);
+        for v in &mut v {
+            *offset += *v;
+            *v = *offset;
+        }
+        _mm256_loadu_ps(v.as_ptr())
+    }
+
+    // fixed_coverage is raster_fixed's coverage, eight lanes at a time.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn fixed_coverage(acc: __m256i, rule: FillRule) -> __m256i {
+        let a = _mm256_abs_epi32(acc);
+        match rule {
+            FillRule::NonZero => a,
+            FillRule::EvenOdd => {
+                let one = _mm256_set1_epi32(1 << (2*ϕ));
+                let two = _mm256_set1_epi32(2 << (2*ϕ));
+                let a = _mm256_and_si256(a, _mm256_sub_epi32(two, _mm256_set1_epi32(1)));
+                _mm256_blendv_epi8(a, _mm256_sub_epi32(two, a), _mm256_cmpgt_epi32(a, one))
+            }
+        }
+    }
+
+    // floating_coverage is raster_floating's clamp_alpha, eight lanes at a time.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn floating_coverage(acc: __m256, rule: FillRule) -> __m256 {
+        let one = _mm256_set1_ps(1.0);
+        let a = _mm256_andnot_ps(_mm256_set1_ps(-0.0), acc);
+        match rule {
+            FillRule::NonZero => _mm256_min_ps(a, one),
+            FillRule::EvenOdd => {
+                // See the SSE2 version.
+                let two = _mm256_set1_ps(2.0);
+                let k = _mm256_round_ps(_mm256_mul_ps(a, _mm256_set1_ps(0.5)), _MM_FROUND_TO_ZERO);
+                let a = _mm256_sub_ps(a, _mm256_mul_ps(k, two));
+                _mm256_blendv_ps(a, _mm256_sub_ps(two, a), _mm256_cmp_ps(a, one, _CMP_GT_OQ))
+            }
+        }
+    }
+
+    // over is the SSE2 over, eight lanes at a time.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn over(d: *const u8, ma: __m256i) -> __m256i {
+        let x = _mm256_cvtepu8_epi32(_mm_loadl_epi64(d as *const __m128i));
+        let dst_a = _mm256_or_si256(x, _mm256_slli_epi32(x, 8));
+        let n = _mm256_mullo_epi32(dst_a, _mm256_sub_epi32(_mm256_set1_epi32(0xffff), ma));
+        let q = _mm256_add_epi32(n, _mm256_srli_epi32(n, 16));
+        let q = _mm256_srli_epi32(_mm256_add_epi32(q, _mm256_set1_epi32(1)), 16);
+        _mm256_srli_epi32(_mm256_add_epi32(q, ma), 8)
+    }
+
+    // store_u8 writes the low bytes of a's lanes to d, like an `as u8` cast.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn store_u8(d: *mut u8, a: __m256i) {
+        let a = _mm256_and_si256(a, _mm256_set1_epi32(0xff));
+        // The packs work within each 128-bit half, leaving lanes 0-3 in the
+        // first four bytes and lanes 4-7 in the bytes 16-19.
+        let a = _mm256_packus_epi16(_mm256_packs_epi32(a, a), a);
+        let a = _mm256_permutevar8x32_epi32(a, _mm256_setr_epi32(0, 4, 0, 0, 0, 0, 0, 0));
+        _mm_storel_epi64(d as *mut __m128i, _mm256_castsi256_si128(a));
+    }
+}
+
+#[cfg(all(test, any(target_arch = "x86", target_arch = "x86_64")))]
+mod tests {
+    use super::*;
+
+    // Kernels are named by their instruction set, and only the ones the CPU
+    // has are run.
+    fn kernels() -> Vec<&'static str> {
+        let mut k = vec!["sse2"];
+        if is_x86_feature_detected!("avx2") { k.push("avx2") }
+        k
+    }
+
+    // rand is xorshift32, to fill the buffers without a dependency.
+    fn rand(state: &mut u32) -> u32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 17;
+        *state ^= *state << 5;
+        *state
+    }
+
+    fn fixed_cells(state: &mut u32, n: usize) -> Vec<u32> {
+        let one = 1i32 << (2*raster_fixed::ϕ);
+        (0..n).map(|_| match rand(state) % 4 {
+            0 => 0,
+            1 => rand(state),
+            _ => ((rand(state) % (6*one as u32)) as i32 - 3*one) as u32,
+        }).collect()
+    }
+
+    fn floating_cells(state: &mut u32, n: usize) -> Vec<f32> {
+        (0..n).map(|_| match rand(state) % 4 {
+            0 => 0.0,
+            1 => (rand(state) % 4) as f32 - 2.0,
+            _ => (rand(state) % 60000) as f32 / 10000.0 - 3.0,
+        }).collect()
+    }
+
+    fn bytes(state: &mut u32, n: usize) -> Vec<u8> {
+        (0..n).map(|_| rand(state) as u8).collect()
+    }
+
+    #[test]
+    fn fixed_matches_scalar() {
+        let mut state = 1;
+        for &rule in &[FillRule::NonZero, FillRule::EvenOdd] {
+            for n in 0..70 {
+                let src = fixed_cells(&mut state, n);
+                let dst = bytes(&mut state, n);
+
+                let mut mask = src.clone();
+                raster_fixed::accumulate_mask(&mut mask, rule);
+                let mut over = dst.clone();
+                raster_fixed::accumulate_op_over(&mut over, &src, rule);
+                let mut src_ = dst.clone();
+                raster_fixed::accumulate_op_src(&mut src_, &src, rule);
+
+                for k in kernels() {
+                    let (mut m, mut o, mut s) = (src.clone(), dst.clone(), dst.clone());
+                    unsafe {
+                        match k {
+                            "sse2" => {
+                                sse2::fixed_accumulate_mask(&mut m, rule);
+                                sse2::fixed_accumulate_op_over(&mut o, &src, rule);
+                                sse2::fixed_accumulate_op_src(&mut s, &src, rule);
+                            }
+                            _ => {
+                                avx2::fixed_accumulate_mask(&mut m, rule);
+                                avx2::fixed_accumulate_op_over(&mut o, &src, rule);
+                                avx2::fixed_accumulate_op_src(&mut s, &src, rule);
+                            }
+                        }
+                    }
+                    assert_eq!(m, mask, "{} mask {:?} {}", k, rule, n);
+                    assert_eq!(o, over, "{} over {:?} {}", k, rule, n);
+                    assert_eq!(s, src_, "{} src {:?} {}", k, rule, n);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn floating_matches_scalar() {
+        let mut state = 2;
+        for &rule in &[FillRule::NonZero, FillRule::EvenOdd] {
+            for n in 0..70 {
+                let src = floating_cells(&mut state, n);
+                let dst = bytes(&mut state, n);
+                let cells: Vec<u32> = src.iter().map(|v| v.to_bits()).collect();
+
+                let mut mask = cells.clone();
+                raster_floating::accumulate_mask_x(&mut mask, rule);
+                let mut over = dst.clone();
+                raster_floating::accumulate_op_over(&mut over, &src, rule);
+                let mut src_ = dst.clone();
+                raster_floating::accumulate_op_src(&mut src_, &src, rule);
+
+                for k in kernels() {
+                    let (mut m, mut o, mut s) = (cells.clone(), dst.clone(), dst.clone());
+                    unsafe {
+                        match k {
+                            "sse2" => {
+                                sse2::floating_accumulate_mask(&mut m, rule);
+                                sse2::floating_accumulate_op_over(&mut o, &src, rule);
+                                sse2::floating_accumulate_op_src(&mut s, &src, rule);
+                            }
+                            _ => {
+                                avx2::floating_accumulate_mask(&mut m, rule);
+                                avx2::floating_accumulate_op_over(&mut o, &src, rule);
+                                avx2::floating_accumulate_op_src(&mut s, &src, rule);
+                            }
+                        }
+                    }
+                    assert_eq!(m, mask, "{} mask {:?} {}", k, rule, n);
+                    assert_eq!(o, over, "{} over {:?} {}", k, rule, n);
+                    assert_eq!(s, src_, "{} src {:?} {}", k, rule, n);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// This file contains a stroker, which turns the outline of a path into a
+// path that can be filled.
+//
+// The path is flattened into polylines and every line segment, join and cap
+// becomes a small convex polygon. All of them wind the same way, so filling
+// the result with the non-zero rule gives their union without any of the
+// bookkeeping an offset-curve stroker needs.
+
+use super::arc;
+use super::flatten::{polylines, DEFAULT_TOLERANCE};
+use super::path::Path;
+
+/// Shape of the ends of open subpaths.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Cap {
+    /// Ends exactly at the end points.
+    Butt,
+    /// Adds a half circle past each end.
+    Round,
+    /// Adds a half square past each end.
+    Square,
+}
+
+/// Shape of the corners between segments.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Join {
+    /// Extends the outer edges until they meet, unless that is longer than
+    /// the miter limit, and then falls back to `Bevel`.
+    Miter,
+    Round,
+    /// Cuts the corner with a straight line.
+    Bevel,
+}
+
+/// Parameters of `Path::stroke`. The default is that of SVG.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Stroke {
+    pub width: f32,
+    pub cap: Cap,
+    pub join: Join,
+    /// Longest miter allowed, as a ratio of the width.
+    pub miter_limit: f32,
+    /// Flattening tolerance for the curves of the path.
+    pub tolerance: f32,
+}
+
+impl Default for Stroke {
+    fn default() -> Self {
+        Self { width: 1.0, cap: Cap::Butt, join: Join::Miter, miter_limit: 4.0, tolerance: DEFAULT_TOLERANCE }
+    }
+}
+
+impl Path {
+    /// Returns the area covered by stroking the path, as a path to be filled
+    /// with the non-zero rule.
+    ///
+    /// Curves are flattened as the Rasterizer flattens them, so the path
+    /// should already be in the Rasterizer's coordinates, and the
+    /// tolerances should match.
+    pub fn stroke(&self, stroke: &Stroke) -> Path {
+        let mut out = Path::new();
+        let hw = stroke.width / 2.0;
+        if hw.is_nan() || hw <= 0.0 {
+            return out
+        }
+        for (pts, closed) in polylines(self, stroke.tolerance) {
+            stroke_polyline(&mut out, &pts, closed, hw, stroke);
+        }
+        out
+    }
+}
+
+fn stroke_polyline(out: &mut Path, pts: &[[f32; 2]], closed: bool, hw: f32, stroke: &Stroke) {
+    if pts.len() == 1 {
+        // A zero length subpath only shows its caps.
+        let p = pts[0];
+        match stroke.cap {
+            Cap::Butt => (),
+            Cap::Round => circle(out, p, hw, stroke.tolerance),
+            Cap::Square => polygon(out, &[
+                [p[0] - hw, p[1] - hw], [p[0] + hw, p[1] - hw],
+                [p[0] + hw, p[1] + hw], [p[0] - hw, p[1] + hw],
+            ]),
+        }
+        return
+    }
+
+    let n = pts.len();
+    let segs = if closed { n } else { n - 1 };
+    let dir = |i: usize| {
+        let (p, q) = (pts[i % n], pts[(i + 1) % n]);
+        let (dx, dy) = (q[0] - p[0], q[1] - p[1]);
+        let len = (dx * dx + dy * dy).sqrt();
+        [dx / len, dy / len]
+    };
+
+    for i in 0..segs {
+        let (p, q) = (pts[i], pts[(i + 1) % n]);
+        let d = dir(i);
+        let nx = [-d[1] * hw, d[0] * hw];
+        polygon(out, &[
+            [p[0] + nx[0], p[1] + nx[1]], [q[0] + nx[0], q[1] + nx[1]],
+            [q[0] - nx[0], q[1] - nx[1]], [p[0] - nx[0], p[1] - nx[1]],
+        ]);
+    }
+
+    // The join at pts[i] is between the segments i - 1 and i.
+    let joins = if closed { 0..n } else { 1..n - 1 };
+    for i in joins {
+        let d0 = dir((i + n - 1) % n);
+        let d1 = dir(i);
+        join(out, pts[i], d0, d1, hw, stroke);
+    }
+
+    if !closed {
+        let d = dir(0);
+        cap(out, pts[0], [-d[0], -d[1]], hw, stroke);
+        let d = dir(n - 2);
+        cap(out, pts[n - 1], d, hw, stroke);
+    }
+}
+
+fn join(out: &mut Path, v: [f32; 2], d0: [f32; 2], d1: [f32; 2], hw: f32, stroke: &Stroke) {
+    let cross = d0[0] * d1[1] - d0[1] * d1[0];
+    let dot = d0[0] * d1[0] + d0[1] * d1[1];
+    if cross.abs() < 1e-6 && dot > 0.0 {
+        return
+    }
+    if stroke.join == Join::Round {
+        return circle(out, v, hw, stroke.tolerance)
+    }
+
+    // The outer side of the corner is away from the turn.
+    let s = if cross > 0.0 { -hw } else { hw };
+    let n0 = [-d0[1] * s, d0[0] * s];
+    let n1 = [-d1[1] * s, d1[0] * s];
+    let a = [v[0] + n0[0], v[1] + n0[1]];
+    let b = [v[0] + n1[0], v[1] + n1[1]];
+
+    // The miter is (n0 + n1) / (1 + cos(turn)), and its length over the
+    // width is 1 / cos(turn / 2).
+    let cos_half = ((1.0 + dot) / 2.0).sqrt();
+    if stroke.join == Join::Miter && cos_half * stroke.miter_limit >= 1.0 {
+        let k = 1.0 / (1.0 + dot);
+        let m = [v[0] + (n0[0] + n1[0]) * k, v[1] + (n0[1] + n1[1]) * k];
+        polygon(out, &[v, a, m, b]);
+    } else {
+        polygon(out, &[v, a, b]);
+    }
+}
+
+// cap adds the cap at the end point p of a subpath that runs in direction d.
+fn cap(out: &mut Path, p: [f32; 2], d: [f32; 2], hw: f32, stroke: &Stroke) {
+    match stroke.cap {
+        Cap::Butt => (),
+        Cap::Round => circle(out, p, hw, stroke.tolerance),
+        Cap::Square => {
+            let (n, e) = ([-d[1] * hw, d[0] * hw], [d[0] * hw, d[1] * hw]);
+            polygon(out, &[
+                [p[0] + n[0], p[1] + n[1]], [p[0] + n[0] + e[0], p[1] + n[1] + e[1]],
+                [p[0] - n[0] + e[0], p[1] - n[1] + e[1]], [p[0] - n[0], p[1] - n[1]],
+            ]);
+        }
+    }
+}
+
+// polygon adds a closed polygon, turned so that it winds the same way as
+// every other piece of the stroke.
+fn polygon(out: &mut Path, pts: &[[f32; 2]]) {
+    let mut area = 0.0;
+    for (i, p) in pts.iter().enumerate() {
+        let q = pts[(i + 1) % pts.len()];
+        area += p[0] * q[1] - q[0] * p[1];
+    }
+    out.move_to(pts[0][0], pts[0][1]);
+    if area > 0.0 {
+        for p in pts[1..].iter().rev() {
+            out.line_to(p[0], p[1]);
+        }
+    } else {
+        for p in &pts[1..] {
+            out.line_to(p[0], p[1]);
+        }
+    }
+    out.close_path();
+}
+
+fn circle(out: &mut Path, c: [f32; 2], r: f32, tolerance: f32) {
+    let start = [c[0] + r, c[1]];
+    out.move_to(start[0], start[1]);
+    out.extend(arc::ellipse(c, [r, r], [0.0, 1.0], 0.0, -2.0 * std::f32::consts::PI, start, tolerance));
+    out.close_path();
+}
@@ -0,0 +1,327 @@
+// This file contains a parser for SVG path data, the `d` attribute of the
+// path element.
+//
+// See https://www.w3.org/TR/SVG11/paths.html#PathDataBNF for the grammar.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::path::Path;
+
+mod document;
+
+pub use self::document::{Document, DocumentError};
+
+/// Error returned by `parse_path`, with the byte offset where parsing stopped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    pub pos: usize,
+    pub msg: &'static str,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.msg, self.pos)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl FromStr for Path {
+    type Err = Error;
+    fn from_str(d: &str) -> Result<Self, Error> { parse_path(d) }
+}
+
+/// Parses SVG path data into a `Path`.
+///
+/// Every command of SVG 1.1 is supported, in both the absolute and the
+/// relative forms, including implicit repetitions, shorthand curves and the
+/// compact number syntax (`M.5.5-1e2,3` or arc flags without separators).
+/// Unlike browsers, which draw the path up to the first error, malformed data
+/// is rejected as a whole.
+pub fn parse_path(d: &str) -> Result<Path, Error> {
+    let mut p = Parser { src: d.as_bytes(), pos: 0 };
+    let mut path = Path::new();
+
+    // The command repeated by extra arguments, and the control point of the
+    // previous curve for the S and T shorthands.
+    let mut cmd: Option<u8> = None;
+    let mut prev: Option<(u8, [f32; 2])> = None;
+
+    loop {
+        p.skip_ws();
+        let c = match p.peek() {
+            Some(c) => c,
+            None => break,
+        };
+        if cmd.is_none() && c != b'M' && c != b'm' {
+            return Err(p.error("path data must start with a moveto"));
+        }
+        let c = if c.is_ascii_alphabetic() {
+            p.pos += 1;
+            c
+        } else {
+            match cmd {
+                Some(c) if c != b'Z' && c != b'z' && p.at_number() => c,
+                _ => return Err(p.error("expected a command")),
+            }
+        };
+
+        let rel = c.is_ascii_lowercase();
+        let pen = path.pen();
+        let abs = |x: f32, y: f32| if rel { [pen[0] + x, pen[1] + y] } else { [x, y] };
+        let mut ctrl = None;
+        match c.to_ascii_uppercase() {
+            b'M' => {
+                let [x, y] = abs(p.number()?, p.number()?);
+                path.move_to(x, y);
+            }
+            b'L' => {
+                let [x, y] = abs(p.number()?, p.number()?);
+                path.line_to(x, y);
+            }
+            b'H' => {
+                let x = p.number()?;
+                let x = if rel { pen[0] + x } else { x };
+                path.line_to(x, pen[1]);
+            }
+            b'V' => {
+                let y = p.number()?;
+                let y = if rel { pen[1] + y } else { y };
+                path.line_to(pen[0], y);
+            }
+            b'C' => {
+                let b = abs(p.number()?, p.number()?);
+                let c = abs(p.number()?, p.number()?);
+                let d = abs(p.number()?, p.number()?);
+                path.cube_to(b[0], b[1], c[0], c[1], d[0], d[1]);
+                ctrl = Some((b'C', c));
+            }
+            b'S' => {
+                let b = reflect(prev, b'C', pen);
+                let c = abs(p.number()?, p.number()?);
+                let d = abs(p.number()?, p.number()?);
+                path.cube_to(b[0], b[1], c[0], c[1], d[0], d[1]);
+                ctrl = Some((b'C', c));
+            }
+            b'Q' => {
+                let b = abs(p.number()?, p.number()?);
+                let c = abs(p.number()?, p.number()?);
+                path.quad_to(b[0], b[1], c[0], c[1]);
+                ctrl = Some((b'Q', b));
+            }
+            b'T' => {
+                let b = reflect(prev, b'Q', pen);
+                let c = abs(p.number()?, p.number()?);
+                path.quad_to(b[0], b[1], c[0], c[1]);
+                ctrl = Some((b'Q', b));
+            }
+            b'A' => {
+                let (rx, ry, rotation) = (p.number()?, p.number()?, p.number()?);
+                let (large, sweep) = (p.flag()?, p.flag()?);
+                let [x, y] = abs(p.number()?, p.number()?);
+                path.svg_arc_to(rx, ry, rotation, large, sweep, x, y);
+            }
+            b'Z' => {
+                path.close_path();
+            }
+            _ => {
+                p.pos -= 1;
+                return Err(p.error("unknown command"));
+            }
+        }
+        prev = ctrl;
+
+        // Extra arguments repeat the command, except that those of a moveto
+        // are linetos. A closepath takes none.
+        cmd = match c {
+            b'M' => Some(b'L'),
+            b'm' => Some(b'l'),
+            c => Some(c),
+        };
+    }
+    Ok(path)
+}
+
+// reflect returns the first control point of a shorthand curve: the
+// reflection of the previous control point about the pen if the previous
+// command was a curve of the same kind, and the pen otherwise.
+fn reflect(prev: Option<(u8, [f32; 2])>, kind: u8, pen: [f32; 2]) -> [f32; 2] {
+    match prev {
+        Some((k, c)) if k == kind => [2.0 * pen[0] - c[0], 2.0 * pen[1] - c[1]],
+        _ => pen,
+    }
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &'static str) -> Error {
+        Error { pos: self.pos, msg }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.pos).cloned()
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') | Some(0x0C) = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn at_number(&self) -> bool {
+        match self.peek() {
+            Some(c) => c.is_ascii_digit() || c == b'.' || c == b'-' || c == b'+',
+            None => false,
+        }
+    }
+
+    // skip_sep skips the whitespace and the optional comma after an argument.
+    // A comma must be followed by another argument.
+    fn skip_sep(&mut self) -> Result<(), Error> {
+        self.skip_ws();
+        if self.peek() == Some(b',') {
+            self.pos += 1;
+            self.skip_ws();
+            if !self.at_number() {
+                return Err(self.error("expected a number"));
+            }
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<f32, Error> {
+        self.skip_ws();
+        let start = self.pos;
+        if let Some(b'-') | Some(b'+') = self.peek() {
+            self.pos += 1;
+        }
+        let int = self.digits();
+        let mut frac = 0;
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            frac = self.digits();
+        }
+        if int == 0 && frac == 0 {
+            self.pos = start;
+            return Err(self.error("expected a number"));
+        }
+
+        // An exponent is only taken when digits follow, so that "1e" is the
+        // number 1 followed by an unknown command.
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            let mark = self.pos;
+            self.pos += 1;
+            if let Some(b'-') | Some(b'+') = self.peek() {
+                self.pos += 1;
+            }
+            if self.digits() == 0 {
+                self.pos = mark;
+            }
+        }
+
+        let s = std::str::from_utf8(&self.src[start..self.pos]).unwrap();
+        let v: f32 = s.parse().map_err(|_| Error { pos: start, msg: "invalid number" })?;
+        if !v.is_finite() {
+            return Err(Error { pos: start, msg: "number out of range" });
+        }
+        self.skip_sep()?;
+        Ok(v)
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    // flag reads an arc flag, a single 0 or 1 that needs no separator.
+    fn flag(&mut self) -> Result<bool, Error> {
+        self.skip_ws();
+        let v = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(self.error("expected a flag")),
+        };
+        self.pos += 1;
+        self.skip_sep()?;
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::path::Segment::{self, *};
+
+    fn segments(d: &str) -> Vec<Segment> {
+        parse_path(d).unwrap().iter().cloned().collect()
+    }
+
+    #[test]
+    fn compact_numbers() {
+        assert_eq!(segments("M.5.5-1e2,3"), [MoveTo([0.5, 0.5]), LineTo([-100.0, 3.0])]);
+        assert_eq!(segments("M1e1-2E-1+3.4.5L0 0"), [MoveTo([10.0, -0.2]), LineTo([3.4, 0.5]), LineTo([0.0, 0.0])]);
+        // An exponent without digits is a command.
+        assert_eq!(parse_path("M1 2e").unwrap_err(), Error { pos: 4, msg: "unknown command" });
+    }
+
+    #[test]
+    fn arc_flags() {
+        let want = segments("M0 0 A5,5 0 1,1 10,0 a 5 5 0 0 0 -10 0");
+        assert_eq!(segments("M0 0A5 5 0 1110 0a5 5 0 00-10 0"), want);
+        assert!(want.len() > 2);
+    }
+
+    #[test]
+    fn implicit_repeats() {
+        assert_eq!(segments("M1 2 3 4 5 6"), [MoveTo([1.0, 2.0]), LineTo([3.0, 4.0]), LineTo([5.0, 6.0])]);
+        assert_eq!(segments("m1 2 3 4 1 1"), [MoveTo([1.0, 2.0]), LineTo([4.0, 6.0]), LineTo([5.0, 7.0])]);
+        assert_eq!(segments("M0 0 L1 1 2 2h1 1v-1"), [
+            MoveTo([0.0, 0.0]), LineTo([1.0, 1.0]), LineTo([2.0, 2.0]),
+            LineTo([3.0, 2.0]), LineTo([4.0, 2.0]), LineTo([4.0, 1.0]),
+        ]);
+    }
+
+    #[test]
+    fn reflection() {
+        assert_eq!(segments("M0 0 C1 2 3 4 5 6 S9 10 11 12"), [
+            MoveTo([0.0, 0.0]),
+            CubeTo([1.0, 2.0], [3.0, 4.0], [5.0, 6.0]),
+            CubeTo([7.0, 8.0], [9.0, 10.0], [11.0, 12.0]),
+        ]);
+        assert_eq!(segments("m0 0 c1 2 3 4 5 6 s4 4 6 6"), segments("M0 0 C1 2 3 4 5 6 S9 10 11 12"));
+        // Without a previous curve of the same kind, the control point is the pen.
+        assert_eq!(segments("M1 1 S2 2 3 3"), [MoveTo([1.0, 1.0]), CubeTo([1.0, 1.0], [2.0, 2.0], [3.0, 3.0])]);
+        assert_eq!(segments("M0 0 Q1 2 3 4 T7 8 T9 10"), [
+            MoveTo([0.0, 0.0]),
+            QuadTo([1.0, 2.0], [3.0, 4.0]),
+            QuadTo([5.0, 6.0], [7.0, 8.0]),
+            QuadTo([9.0, 10.0], [9.0, 10.0]),
+        ]);
+        assert_eq!(segments("M0 0 C1 2 3 4 5 6 T7 8")[2], QuadTo([5.0, 6.0], [7.0, 8.0]));
+    }
+
+    #[test]
+    fn errors() {
+        for &(d, pos, msg) in &[
+            ("L1 2", 0, "path data must start with a moveto"),
+            ("M1 2 X3 4", 5, "unknown command"),
+            ("M1,2,", 5, "expected a number"),
+            ("M1 2 L3", 7, "expected a number"),
+            ("M1 2 L3,,4", 8, "expected a number"),
+            ("M0 0 A1 1 0 2 0 1 1", 12, "expected a flag"),
+            ("M0 0 Z 1", 7, "expected a command"),
+            ("M0 0 1e999 3", 5, "number out of range"),
+            ("M0 0 L-1e39 3", 6, "number out of range"),
+        ] {
+            assert_eq!(parse_path(d).unwrap_err(), Error { pos, msg }, "{}", d);
+        }
+    }
+}
@@ -0,0 +1,569 @@
+// This file contains a renderer for a small subset of SVG documents: the
+// basic shapes, paths and groups, with solid fills and strokes.
+//
+// See https://www.w3.org/TR/SVG11/ for the specification.
+
+use std::fmt;
+
+use crate::Canvas;
+use crate::geom::Transform;
+use crate::image::{Rectangle, RGBA};
+use crate::xml::{self, Element};
+use crate::vg::{FillRule, Rasterizer};
+use crate::vg::path::Path;
+use crate::vg::stroke::{Cap, Join, Stroke};
+use super::{parse_path, Parser};
+
+/// A parsed SVG document, ready to be drawn at any size.
+///
+/// Supported are the `rect`, `circle`, `ellipse`, `line`, `polyline`,
+/// `polygon` and `path` elements, nested in `g` groups, with the
+/// `transform` attribute and these properties, as attributes or in the
+/// `style` attribute: `fill`, `fill-opacity`, `fill-rule`, `stroke`,
+/// `stroke-opacity`, `stroke-width`, `stroke-linecap`, `stroke-linejoin`,
+/// `stroke-miterlimit`, `opacity`, `color` and `display`.
+///
+/// Paints are solid colors only: a gradient or pattern reference gives its
+/// fallback color, or nothing. Group opacity is applied to every shape of the
+/// group separately, so overlapping shapes show through each other.
+#[derive(Clone, Debug)]
+pub struct Document {
+    /// Size of the viewport, from the `width` and `height` of the root
+    /// element, or the `viewBox` when those are missing.
+    pub width: f32,
+    pub height: f32,
+    /// Area of the user space shown in the viewport, as `[x, y, w, h]`.
+    pub view_box: [f32; 4],
+    /// Whether the `viewBox` is stretched to the viewport, instead of being
+    /// scaled uniformly and centered.
+    pub stretch: bool,
+    shapes: Vec<Shape>,
+}
+
+#[derive(Clone, Debug)]
+struct Shape {
+    /// In user space, with the transforms of the element and its groups applied.
+    path: Path,
+    /// Alpha-premultiplied 16-bit colors.
+    fill: Option<[u32; 4]>,
+    fill_rule: FillRule,
+    stroke: Option<([u32; 4], Stroke)>,
+}
+
+impl Document {
+    /// Parses an SVG document.
+    pub fn parse(src: &str) -> Result<Self, DocumentError> {
+        let root = xml::parse(src)?;
+        if local_name(&root.name) != "svg" {
+            return Err(DocumentError::Format("the root element isn't svg"));
+        }
+
+        let view_box = match root.attr("viewBox") {
+            Some(v) => {
+                let v = numbers(v).ok_or(DocumentError::Format("invalid viewBox"))?;
+                if v.len() != 4 || !(v[2] > 0.0 && v[3] > 0.0) {
+                    return Err(DocumentError::Format("invalid viewBox"));
+                }
+                Some([v[0], v[1], v[2], v[3]])
+            }
+            None => None,
+        };
+        let width = root.attr("width").and_then(length);
+        let height = root.attr("height").and_then(length);
+        let width = width.or_else(|| view_box.map(|v| v[2])).unwrap_or(100.0);
+        let height = height.or_else(|| view_box.map(|v| v[3])).unwrap_or(100.0);
+        let stretch = root.attr("preserveAspectRatio").map(str::trim) == Some("none");
+
+        let mut doc = Self {
+            width,
+            height,
+            view_box: view_box.unwrap_or([0.0, 0.0, width, height]),
+            stretch,
+            shapes: Vec::new(),
+        };
+        doc.group(&root, &Style::new(&root, &Style::default()), Transform::IDENTITY)?;
+        Ok(doc)
+    }
+
+    /// Draws the document over `dst`, with the viewport filling `r`.
+    pub fn draw(&self, dst: &mut RGBA, r: Rectangle) {
+        if r.is_empty() {
+            return
+        }
+        let [vx, vy, vw, vh] = self.view_box;
+        let (sx, sy) = (r.dx() as f32 / vw, r.dy() as f32 / vh);
+        let m = if self.stretch {
+            [sx, 0.0, 0.0, sy, -vx * sx, -vy * sy]
+        } else {
+            let s = sx.min(sy);
+            let ox = (r.dx() as f32 - vw * s) / 2.0;
+            let oy = (r.dy() as f32 - vh * s) / 2.0;
+            [s, 0.0, 0.0, s, ox - vx * s, oy - vy * s]
+        };
+        let scale = (m[0] * m[3]).abs().sqrt();
+
+        let mut rs = Rasterizer::new(r.dx() as usize, r.dy() as usize);
+        for shape in &self.shapes {
+            let mut path = shape.path.clone();
+            path.transform(m);
+            if let Some(color) = shape.fill {
+                rs.clear();
+                rs.fill_rule = shape.fill_rule;
+                rs.add_path(&path);
+                rs.rgba_uniform_over(dst, r, color);
+            }
+            if let Some((color, mut stroke)) = shape.stroke {
+                stroke.width *= scale;
+                rs.clear();
+                rs.fill_rule = FillRule::NonZero;
+                rs.add_path(&path.stroke(&stroke));
+                rs.rgba_uniform_over(dst, r, color);
+            }
+        }
+    }
+
+    /// Draws the document over the whole canvas.
+    pub fn render(&self, canvas: &mut Canvas) {
+        let r = canvas.bounds();
+        canvas.draw_rgba(r, |dst| self.draw(dst, r));
+    }
+
+    fn group(&mut self, el: &Element, parent: &Style, ctm: Transform) -> Result<(), DocumentError> {
+        for child in &el.children {
+            let style = Style::new(child, parent);
+            if !style.display {
+                continue
+            }
+            let ctm = match child.attr("transform") {
+                Some(t) => ctm * transform(t).ok_or(DocumentError::Format("invalid transform"))?,
+                None => ctm,
+            };
+            match local_name(&child.name) {
+                "g" => self.group(child, &style, ctm)?,
+                name => {
+                    if let Some(mut path) = shape(name, child)? {
+                        path.transform(ctm);
+                        self.push(path, &style, ctm);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, path: Path, style: &Style, ctm: Transform) {
+        let fill = style.fill.map(|c| premultiply(c, style.opacity * style.fill_opacity));
+        let stroke = style.stroke.map(|c| {
+            let color = premultiply(c, style.opacity * style.stroke_opacity);
+            let scale = ctm.determinant().abs().sqrt();
+            (color, Stroke { width: style.line.width * scale, ..style.line })
+        });
+        if fill.is_some() || stroke.is_some() {
+            self.shapes.push(Shape { path, fill, fill_rule: style.fill_rule, stroke });
+        }
+    }
+}
+
+// shape returns the outline of a basic shape or path element in its own
+// coordinates, or None for elements that draw nothing.
+fn shape(name: &str, el: &Element) -> Result<Option<Path>, DocumentError> {
+    let num = |name: &str| el.attr(name).and_then(length).unwrap_or(0.0);
+    let mut path = Path::new();
+    match name {
+        "path" => {
+            path = parse_path(el.attr("d").unwrap_or(""))?;
+        }
+        "rect" => {
+            let (x, y, w, h) = (num("x"), num("y"), num("width"), num("height"));
+            if !(w > 0.0 && h > 0.0) {
+                return Ok(None)
+            }
+            let rx = el.attr("rx").and_then(length);
+            let ry = el.attr("ry").and_then(length);
+            let (rx, ry) = match (rx, ry) {
+                (Some(rx), Some(ry)) => (rx, ry),
+                (Some(r), None) | (None, Some(r)) => (r, r),
+                (None, None) => (0.0, 0.0),
+            };
+            let (rx, ry) = (rx.max(0.0).min(w / 2.0), ry.max(0.0).min(h / 2.0));
+            path.move_to(x + rx, y)
+                .line_to(x + w - rx, y)
+                .svg_arc_to(rx, ry, 0.0, false, true, x + w, y + ry)
+                .line_to(x + w, y + h - ry)
+                .svg_arc_to(rx, ry, 0.0, false, true, x + w - rx, y + h)
+                .line_to(x + rx, y + h)
+                .svg_arc_to(rx, ry, 0.0, false, true, x, y + h - ry)
+                .line_to(x, y + ry)
+                .svg_arc_to(rx, ry, 0.0, false, true, x + rx, y)
+                .close_path();
+        }
+        "circle" | "ellipse" => {
+            let (cx, cy) = (num("cx"), num("cy"));
+            let (rx, ry) = if name == "circle" {
+                (num("r"), num("r"))
+            } else {
+                (num("rx"), num("ry"))
+            };
+            if !(rx > 0.0 && ry > 0.0) {
+                return Ok(None)
+            }
+            path.move_to(cx + rx, cy)
+                .svg_arc_to(rx, ry, 0.0, false, true, cx - rx, cy)
+                .svg_arc_to(rx, ry, 0.0, false, true, cx + rx, cy)
+                .close_path();
+        }
+        "line" => {
+            path.move_to(num("x1"), num("y1")).line_to(num("x2"), num("y2"));
+        }
+        "polyline" | "polygon" => {
+            let pts = numbers(el.attr("points").unwrap_or(""))
+                .ok_or(DocumentError::Format("invalid points"))?;
+            // An odd number of coordinates draws up to the last full point.
+            for (i, p) in pts.chunks_exact(2).enumerate() {
+                if i == 0 {
+                    path.move_to(p[0], p[1]);
+                } else {
+                    path.line_to(p[0], p[1]);
+                }
+            }
+            if name == "polygon" && !path.is_empty() {
+                path.close_path();
+            }
+        }
+        _ => return Ok(None),
+    }
+    Ok(Some(path))
+}
+
+/// The properties of an element, after inheritance.
+#[derive(Clone, Debug)]
+struct Style {
+    /// Colors are non-premultiplied RGBA.
+    fill: Option<[u8; 4]>,
+    fill_opacity: f32,
+    fill_rule: FillRule,
+    stroke: Option<[u8; 4]>,
+    stroke_opacity: f32,
+    line: Stroke,
+    color: [u8; 4],
+    /// Product of the opacity of the element and of its groups.
+    opacity: f32,
+    display: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: Some(BLACK),
+            fill_opacity: 1.0,
+            fill_rule: FillRule::NonZero,
+            stroke: None,
+            stroke_opacity: 1.0,
+            line: Stroke::default(),
+            color: BLACK,
+            opacity: 1.0,
+            display: true,
+        }
+    }
+}
+
+const BLACK: [u8; 4] = [0, 0, 0, 0xFF];
+
+impl Style {
+    fn new(el: &Element, parent: &Self) -> Self {
+        let mut style = Self { display: true, ..parent.clone() };
+
+        // Declarations in the style attribute win over the attributes, and
+        // color goes first, as the other properties may refer to it.
+        let mut decls: Vec<(&str, &str)> = el.attrs.iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        if let Some(css) = el.attr("style") {
+            decls.extend(css.split(';').filter_map(|d| {
+                let mut kv = d.splitn(2, ':');
+                Some((kv.next()?.trim(), kv.next()?.trim()))
+            }));
+        }
+        if let Some(&(_, v)) = decls.iter().rev().find(|(k, _)| *k == "color") {
+            if let Some(c) = color(v) {
+                style.color = c;
+            }
+        }
+        let mut opacity = 1.0;
+        for (k, v) in decls {
+            let v = v.trim();
+            if v == "inherit" {
+                continue
+            }
+            match k {
+                "fill" => if let Some(p) = paint(v, style.color) { style.fill = p },
+                "stroke" => if let Some(p) = paint(v, style.color) { style.stroke = p },
+                "fill-opacity" => if let Some(a) = alpha(v) { style.fill_opacity = a },
+                "stroke-opacity" => if let Some(a) = alpha(v) { style.stroke_opacity = a },
+                "opacity" => if let Some(a) = alpha(v) { opacity = a },
+                "fill-rule" => match v {
+                    "nonzero" => style.fill_rule = FillRule::NonZero,
+                    "evenodd" => style.fill_rule = FillRule::EvenOdd,
+                    _ => (),
+                },
+                "stroke-width" => if let Some(w) = length(v) { style.line.width = w },
+                "stroke-linecap" => match v {
+                    "butt" => style.line.cap = Cap::Butt,
+                    "round" => style.line.cap = Cap::Round,
+                    "square" => style.line.cap = Cap::Square,
+                    _ => (),
+                },
+                "stroke-linejoin" => match v {
+                    "miter" => style.line.join = Join::Miter,
+                    "round" => style.line.join = Join::Round,
+                    "bevel" => style.line.join = Join::Bevel,
+                    _ => (),
+                },
+                "stroke-miterlimit" => match v.parse::<f32>() {
+                    Ok(l) if l >= 1.0 => style.line.miter_limit = l,
+                    _ => (),
+                },
+                "display" => style.display = v != "none",
+                _ => (),
+            }
+        }
+        style.opacity *= opacity;
+        style
+    }
+}
+
+// paint parses a fill or stroke value: Some(None) is none, and None is an
+// invalid value, which leaves the inherited one.
+fn paint(v: &str, current: [u8; 4]) -> Option<Option<[u8; 4]>> {
+    if v == "none" {
+        return Some(None)
+    }
+    if v == "currentColor" {
+        return Some(Some(current))
+    }
+    if v.starts_with("url(") {
+        let fallback = v[v.find(')')? + 1..].trim();
+        return if fallback.is_empty() { Some(None) } else { paint(fallback, current) }
+    }
+    color(v).map(Some)
+}
+
+fn color(v: &str) -> Option<[u8; 4]> {
+    if let Some(hex) = v.strip_prefix('#') {
+        let n = u32::from_str_radix(hex, 16).ok()?;
+        return match hex.len() {
+            3 => {
+                let c = |i: u32| (((n >> (8 - 4 * i)) & 0xF) * 0x11) as u8;
+                Some([c(0), c(1), c(2), 0xFF])
+            }
+            6 => Some([(n >> 16) as u8, (n >> 8) as u8, n as u8, 0xFF]),
+            _ => None,
+        }
+    }
+    if v.starts_with("rgb(") || v.starts_with("rgba(") {
+        let args = &v[v.find('(')? + 1..v.rfind(')')?];
+        let args: Vec<&str> = args.split(',').map(str::trim).collect();
+        if args.len() < 3 || args.len() > 4 {
+            return None
+        }
+        let mut c = [0xFF; 4];
+        for i in 0..3 {
+            let v = match args[i].trim_end_matches('%') {
+                p if p.len() != args[i].len() => p.parse::<f32>().ok()? * 2.55,
+                p => p.parse::<f32>().ok()?,
+            };
+            c[i] = v.clamp(0.0, 255.0).round() as u8;
+        }
+        if let Some(a) = args.get(3) {
+            c[3] = (alpha(a)? * 255.0).round() as u8;
+        }
+        return Some(c)
+    }
+    let rgb = match v.to_ascii_lowercase().as_str() {
+        "transparent" => return Some([0; 4]),
+        "black" => 0x000000,
+        "silver" => 0xC0C0C0,
+        "gray" | "grey" => 0x808080,
+        "white" => 0xFFFFFF,
+        "maroon" => 0x800000,
+        "red" => 0xFF0000,
+        "purple" => 0x800080,
+        "fuchsia" | "magenta" => 0xFF00FF,
+        "green" => 0x008000,
+        "lime" => 0x00FF00,
+        "olive" => 0x808000,
+        "yellow" => 0xFFFF00,
+        "navy" => 0x000080,
+        "blue" => 0x0000FF,
+        "teal" => 0x008080,
+        "aqua" | "cyan" => 0x00FFFF,
+        "orange" => 0xFFA500,
+        _ => return None,
+    };
+    Some([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 0xFF])
+}
+
+// alpha parses an opacity, a number or a percentage clamped to [0, 1].
+fn alpha(v: &str) -> Option<f32> {
+    let a = match v.trim_end_matches('%') {
+        p if p.len() != v.len() => p.parse::<f32>().ok()? / 100.0,
+        p => p.parse::<f32>().ok()?,
+    };
+    Some(a.clamp(0.0, 1.0))
+}
+
+fn premultiply(c: [u8; 4], opacity: f32) -> [u32; 4] {
+    let a = c[3] as f32 / 255.0 * opacity;
+    let k = |v: u8| (v as f32 * 257.0 * a + 0.5) as u32;
+    [k(c[0]), k(c[1]), k(c[2]), (0xFFFF as f32 * a + 0.5) as u32]
+}
+
+// length parses a length in user units. Percentages, and the units that
+// depend on the font, are not supported.
+fn length(v: &str) -> Option<f32> {
+    let mut p = Parser { src: v.as_bytes(), pos: 0 };
+    let n = p.number().ok()?;
+    let k = match v[p.pos..].trim() {
+        "" | "px" => 1.0,
+        "pt" => 4.0 / 3.0,
+        "pc" => 16.0,
+        "mm" => 96.0 / 25.4,
+        "cm" => 96.0 / 2.54,
+        "in" => 96.0,
+        _ => return None,
+    };
+    Some(n * k)
+}
+
+// numbers parses a list of numbers separated by whitespace or commas.
+fn numbers(v: &str) -> Option<Vec<f32>> {
+    let mut p = Parser { src: v.as_bytes(), pos: 0 };
+    let mut out = Vec::new();
+    p.skip_ws();
+    while p.peek().is_some() {
+        out.push(p.number().ok()?);
+    }
+    Some(out)
+}
+
+// transform parses a transform list into a single matrix.
+fn transform(v: &str) -> Option<Transform> {
+    let mut p = Parser { src: v.as_bytes(), pos: 0 };
+    let mut m = Transform::IDENTITY;
+    loop {
+        p.skip_ws();
+        if p.peek() == Some(b',') {
+            p.pos += 1;
+            p.skip_ws();
+        }
+        if p.peek().is_none() {
+            return Some(m)
+        }
+        let start = p.pos;
+        while let Some(b'a'..=b'z') | Some(b'A'..=b'Z') = p.peek() {
+            p.pos += 1;
+        }
+        let name = &v[start..p.pos];
+        p.skip_ws();
+        if p.peek() != Some(b'(') {
+            return None
+        }
+        p.pos += 1;
+        let mut args = Vec::new();
+        loop {
+            p.skip_ws();
+            if p.peek() == Some(b')') {
+                p.pos += 1;
+                break
+            }
+            args.push(p.number().ok()?);
+        }
+
+        let t = match (name, args.as_slice()) {
+            ("matrix", &[a, b, c, d, e, f]) => Transform::new(a, b, c, d, e, f),
+            ("translate", &[x]) => Transform::translate(x, 0.0),
+            ("translate", &[x, y]) => Transform::translate(x, y),
+            ("scale", &[s]) => Transform::scale(s, s),
+            ("scale", &[x, y]) => Transform::scale(x, y),
+            ("rotate", &[a]) => Transform::rotate(a.to_radians()),
+            ("rotate", &[a, cx, cy]) => Transform::rotate_around(a.to_radians(), cx, cy),
+            ("skewX", &[a]) => Transform::skew(a.to_radians(), 0.0),
+            ("skewY", &[a]) => Transform::skew(0.0, a.to_radians()),
+            _ => return None,
+        };
+        m *= t;
+    }
+}
+
+// local_name strips the namespace prefix of an element name.
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Error returned by `Document::parse`.
+#[derive(Debug)]
+pub enum DocumentError {
+    Xml(xml::Error),
+    Path(super::Error),
+    Format(&'static str),
+}
+
+impl From<xml::Error> for DocumentError {
+    fn from(err: xml::Error) -> Self { DocumentError::Xml(err) }
+}
+
+impl From<super::Error> for DocumentError {
+    fn from(err: super::Error) -> Self { DocumentError::Path(err) }
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DocumentError::Xml(err) => write!(f, "invalid XML: {}", err),
+            DocumentError::Path(err) => write!(f, "invalid path data: {}", err),
+            DocumentError::Format(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn red_rect_0rgb() {
+        let doc = Document::parse(r#"<svg width="4" height="4"><rect width="4" height="4" fill="red"/></svg>"#).unwrap();
+        let mut buf = vec![0; 4 * 4];
+        let r = Rectangle::from_size(4, 4);
+        crate::image::draw_0rgb(&mut buf, r, r, |dst| doc.draw(dst, r));
+        for &p in &buf {
+            assert_eq!(p & 0xFF_FFFF, 0xFF_0000);
+        }
+    }
+
+    #[test]
+    fn transforms() {
+        let near = |t: &str, p: [f32; 2], want: [f32; 2]| {
+            let got = transform(t).unwrap().apply(p);
+            assert!((got[0] - want[0]).abs() < 1e-4 && (got[1] - want[1]).abs() < 1e-4, "{}: {:?}", t, got);
+        };
+        near("translate(1 2) scale(2)", [1.0, 1.0], [3.0, 4.0]);
+        near("scale(2, 3),translate(1)", [0.0, 1.0], [2.0, 3.0]);
+        near("rotate(90 10 10)", [20.0, 10.0], [10.0, 20.0]);
+        near("rotate(90)", [1.0, 0.0], [0.0, 1.0]);
+        near("skewX(45)", [1.0, 1.0], [2.0, 1.0]);
+        near("skewY(45)", [1.0, 1.0], [1.0, 2.0]);
+        near("matrix(1 2 3 4 5 6)", [1.0, 1.0], [9.0, 12.0]);
+        assert_eq!(transform("rotate(1 2)"), None);
+    }
+
+    #[test]
+    fn root_style() {
+        let doc = Document::parse(r##"<svg width="4" height="4" fill="#00f"><rect width="4" height="4"/></svg>"##).unwrap();
+        let mut buf = vec![0; 4 * 4 * 4];
+        doc.draw(&mut RGBA::from_buf(&mut buf, Rectangle::from_size(4, 4)), Rectangle::from_size(4, 4));
+        assert_eq!(&buf[..4], &[0, 0, 0xFF, 0xFF]);
+    }
+}
@@ -1,5 +1,7 @@
-use super::{lerp, dev_squared, Rasterizer, Op, SimdVec};
-use crate::image::{RGBA, Rectangle, Point};
+use std::ops::Range;
+
+use super::{flatten, widen, simd, Rasterizer, Op, FillRule, SimdVec};
+use crate::image::{Alpha, RGBA, Rectangle, Point};
 
 /*
 
@@ -56,42 +58,75 @@ impl Rasterizer {
     pub fn new(w: usize, h: usize) -> Self {
         Self {
             size: [w, h],
+            dirty: Rectangle::default(),
+            accumulated: false,
             first: [0.0, 0.0],
             pen: [0.0, 0.0],
             draw_op: Op::Over,
+            fill_rule: FillRule::NonZero,
+            tolerance: flatten::DEFAULT_TOLERANCE,
             use_fpm: w > FPM_THRESHOLD || h > FPM_THRESHOLD,
-            buf: SimdVec::new(w * h),
+            buf: SimdVec::new(0),
+            area: Rectangle::default(),
         }
     }
 
     /// Reset resets a Rasterizer as if it was just returned by NewRasterizer.
     pub fn reset(&mut self, w: usize, h: usize, op: Op) {
+        if self.size == [w, h] {
+            // The stored cells are kept for the next path, and only the dirty
+            // ones need to be zeroed.
+            let spans = self.dirty_spans();
+            let buf = self.buf.as_u32();
+            for (_, span) in spans {
+                buf[span].iter_mut().for_each(|v| *v = 0);
+            }
+        } else {
+            self.buf.recycle(0);
+            self.area = Rectangle::default();
+        }
+        self.dirty = Rectangle::default();
+        self.accumulated = false;
         self.size = [w, h];
         self.first = [0.0, 0.0];
         self.pen = [0.0, 0.0];
         self.draw_op = op;
+        self.fill_rule = FillRule::NonZero;
+        self.tolerance = flatten::DEFAULT_TOLERANCE;
         self.use_fpm = w > FPM_THRESHOLD || h > FPM_THRESHOLD;
-        self.buf.recycle(w * h);
     }
 
+    /// Clears the path, keeping the size, the fill rule and the tolerance.
     pub fn clear(&mut self) {
         let [w, h] = self.size;
+        let (rule, tolerance) = (self.fill_rule, self.tolerance);
         self.reset(w, h, Op::Over);
+        self.fill_rule = rule;
+        self.tolerance = tolerance;
     }
 
     /// Returns the width and height passed to NewRasterizer or Reset.
     pub fn size(&self) -> [usize; 2] { self.size }
 
+    /// Returns the stored cells, the ones of `mask_rect` row by row.
     pub fn as_mask_f32(&self) -> &[f32] { self.buf.as_slice_f32() }
+    /// Returns the stored cells, the ones of `mask_rect` row by row.
     pub fn as_mask_u32(&self) -> &[u32] { self.buf.as_slice_u32() }
 
-    /*
-    // Bounds returns the rectangle from (0, 0) to the width and height passed to
-    // NewRasterizer or Reset.
-    fn (z *Rasterizer) Bounds() image.Rectangle {
-        return image.Rectangle{Max: z.size}
+    /// Returns the cells that are stored. It contains `dirty_rect`, and the
+    /// mask is zero outside of it.
+    pub fn mask_rect(&self) -> Rectangle { self.area }
+
+    /// Returns the rectangle from (0, 0) to the width and height passed to new or reset.
+    pub fn bounds(&self) -> Rectangle {
+        Rectangle::from_size(self.size[0] as isize, self.size[1] as isize)
     }
-    */
+
+    /// Returns the cells that the path may cover.
+    ///
+    /// The mask is zero outside of this rectangle, which is usually much
+    /// smaller than the bounds for small shapes on a large rasterizer.
+    pub fn dirty_rect(&self) -> Rectangle { self.dirty }
 
     /// Returns the location of the path-drawing pen: the last argument to the most recent XxxTo call.
     pub fn pen(&self) -> [f32; 2] { self.pen }
@@ -112,6 +147,8 @@ impl Rasterizer {
     /// Adds a line segment, from the pen to (bx, by), and moves the pen to (bx, by).
     ///
     /// The coordinates are allowed to be out of the Rasterizer's bounds.
+    /// Once the path has been drawn, `clear` has to be called before adding
+    /// to it again.
     pub fn line_to(&mut self, bx: f32, by: f32) {
         if self.use_fpm {
             self.floating_line_to(bx, by)
@@ -124,23 +161,9 @@ impl Rasterizer {
     ///
     /// The coordinates are allowed to be out of the Rasterizer's bounds.
     pub fn quad_to(&mut self, bx: f32, by: f32, cx: f32, cy: f32) {
-        let [ax, ay] = self.pen;
-        let devsq = dev_squared(ax, ay, bx, by, cx, cy);
-
-        if devsq >= 0.333 {
-            const TOL: f64 = 3f64;
-            let n = 1 + (TOL * devsq as f64).sqrt().sqrt() as isize;
-            let (mut t, n_inv) = (0.0, 1.0 / n as f32);
-            for _ in 0..n-1 {
-                t += n_inv;
-                let (abx, aby) = lerp(t, ax, ay, bx, by);
-                let (bcx, bcy) = lerp(t, bx, by, cx, cy);
-                let (bx, by) = lerp(t, abx, aby, bcx, bcy);
-                self.line_to(bx, by);
-            }
+        for [x, y] in flatten::quad(self.pen, [bx, by], [cx, cy], self.tolerance) {
+            self.line_to(x, y);
         }
-
-        self.line_to(cx, cy);
     }
 
     /// Adds a cubic Bézier segment,
@@ -148,27 +171,18 @@ impl Rasterizer {
     ///
     /// The coordinates are allowed to be out of the Rasterizer's bounds.
     pub fn cube_to(&mut self, bx: f32, by: f32, cx: f32, cy: f32, dx: f32, dy: f32) {
-        let [ax, ay] = self.pen;
-        let devsq = dev_squared(ax, ay, bx, by, dx, dy);
-        let devsq_alt = dev_squared(ax, ay, cx, cy, dx, dy);
-        let devsq = if devsq < devsq_alt { devsq_alt } else { devsq };
-
-        if devsq >= 0.333 {
-            const TOL: f64 = 3f64;
-            let n = 1 + (TOL * devsq as f64).sqrt().sqrt() as isize;
-            let (mut t, n_inv) = (0.0, 1.0 / n as f32);
-            for _ in 0..n-1 {
-                t += n_inv;
-                let (abx, aby) = lerp(t, ax, ay, bx, by);
-                let (bcx, bcy) = lerp(t, bx, by, cx, cy);
-                let (cdx, cdy) = lerp(t, cx, cy, dx, dy);
-                let (abcx, abcy) = lerp(t, abx, aby, bcx, bcy);
-                let (bcdx, bcdy) = lerp(t, bcx, bcy, cdx, cdy);
-                let (bx, by) = lerp(t, abcx, abcy, bcdx, bcdy);
-                self.line_to(bx, by)
-            }
+        for [x, y] in flatten::cube(self.pen, [bx, by], [cx, cy], [dx, dy], self.tolerance) {
+            self.line_to(x, y);
+        }
+    }
+
+    /// Fills the accumulated path with the pixels of `src`, mapping `r.min` to `sp`,
+    /// using `draw_op` to combine them with `dst`.
+    pub fn draw(&mut self, dst: &mut RGBA, r: Rectangle, src: &RGBA, sp: Point) {
+        match self.draw_op {
+            Op::Over => self.rasterize_op_over(dst, r, src, sp),
+            Op::Src => self.rasterize_op_src(dst, r, src, sp),
         }
-        self.line_to(dx, dy);
     }
 
     /*
@@ -212,109 +226,143 @@ impl Rasterizer {
     }
     */
 
-    fn accumulate_mask(&mut self) {
-        let simd = false;
-        if simd {
-            unimplemented!("SIMD version")
-        } else {
+    // accumulate_mask turns the area values of buf into coverage, once per
+    // path.
+    pub(crate) fn accumulate_mask(&mut self) {
+        if self.accumulated {
+            return
+        }
+        self.accumulated = true;
+        let (spans, rule) = (self.dirty_spans(), self.fill_rule);
+        let buf = self.buf.as_u32();
+        for (_, span) in spans {
             if self.use_fpm {
-                self.floating_accumulate_mask()
+                simd::floating_accumulate_mask(&mut buf[span], rule)
             } else {
-                self.fixed_accumulate_mask()
+                simd::fixed_accumulate_mask(&mut buf[span], rule)
             }
         }
     }
 
-    /*
-    fn (z *Rasterizer) rasterizeDstAlphaSrcOpaqueOpOver(dst *image.Alpha, r image.Rectangle) {
-        // TODO: non-zero vs even-odd winding?
-        if r == dst.Bounds() && r == z.Bounds() {
-            // We bypass the z.accumulateMask step and convert straight from
-            // z.bufF32 or z.bufU32 to dst.Pix.
-            if z.useFloatingPointMath {
-                if haveFloatingAccumulateSIMD {
-                    floatingAccumulateOpOverSIMD(dst.Pix, z.bufF32)
-                } else {
-                    floatingAccumulateOpOver(dst.Pix, z.bufF32)
-                }
-            } else {
-                if haveFixedAccumulateSIMD {
-                    fixedAccumulateOpOverSIMD(dst.Pix, z.bufU32)
+    // dirty_spans returns the dirty cells of every row, as the index of the
+    // first one in a buffer of the whole bounds and the range of buf holding
+    // them.
+    //
+    // Cells never spill from one row to the next, so the rows are accumulated
+    // separately.
+    pub(super) fn dirty_spans(&self) -> impl Iterator<Item = (usize, Range<usize>)> {
+        let (w, d, a) = (self.size[0] as isize, self.dirty, self.area);
+        (d.min.y..d.max.y).map(move |y| {
+            let i = ((y - a.min.y) * a.dx() + d.min.x - a.min.x) as usize;
+            ((y * w + d.min.x) as usize, i..i + d.dx() as usize)
+        })
+    }
+
+    // mask_at returns the accumulated coverage of the cell (x, y), which is
+    // zero for cells that are not stored.
+    #[inline(always)]
+    fn mask_at(&self, x: isize, y: isize) -> u32 {
+        let a = self.area;
+        if x < a.min.x || x >= a.max.x || y < a.min.y || y >= a.max.y {
+            return 0
+        }
+        self.buf.as_slice_u32()[((y - a.min.y) * a.dx() + x - a.min.x) as usize]
+    }
+
+    /// Composites the coverage of the accumulated path, as an opaque source, over `dst`.
+    ///
+    /// This is the fast path for glyph rendering: when `r` is both the bounds of `dst`
+    /// and of the rasterizer, coverage is written straight from the cell buffer,
+    /// unless the path was already drawn in another way.
+    pub fn alpha_opaque_over(&mut self, dst: &mut Alpha, r: Rectangle) {
+        if r == dst.bounds() && r == self.bounds() && dst.stride == r.dx() && !self.accumulated {
+            // We bypass the accumulate_mask step and convert straight from
+            // the f32 or u32 cells to dst.pix.
+            for (i, span) in self.dirty_spans() {
+                let pix = &mut dst.pix[i..i + span.len()];
+                if self.use_fpm {
+                    simd::floating_accumulate_op_over(pix, &self.buf.as_slice_f32()[span], self.fill_rule)
                 } else {
-                    fixedAccumulateOpOver(dst.Pix, z.bufU32)
+                    simd::fixed_accumulate_op_over(pix, &self.buf.as_slice_u32()[span], self.fill_rule)
                 }
             }
             return
         }
 
-        z.accumulateMask()
-        pix := dst.Pix[dst.PixOffset(r.Min.X, r.Min.Y):]
-        for y, y1 := 0, r.Max.Y-r.Min.Y; y < y1; y++ {
-            for x, x1 := 0, r.Max.X-r.Min.X; x < x1; x++ {
-                ma := z.bufU32[y*z.size.X+x]
-                i := y*dst.Stride + x
-
-                // This formula is like rasterizeOpOver's, simplified for the
+        self.accumulate_mask();
+        let c = self.clip(r, dst.rect).intersect(self.dirty + r.min);
+        for y in c.min.y..c.max.y {
+            let my = y - r.min.y;
+            let i = dst.pix_offset(0, y);
+            for x in c.min.x..c.max.x {
+                let ma = self.mask_at(x - r.min.x, my);
+                let i = (i + x) as usize;
+
+                // This formula is like rasterize_op_over's, simplified for the
                 // concrete dst type and opaque src assumption.
-                a := 0xffff - ma
-                pix[i] = uint8((uint32(pix[i])*0x101*a/0xffff + ma) >> 8)
+                let a = 0xffff - ma;
+                dst.pix[i] = (((dst.pix[i] as u32) * 0x101 * a / 0xffff + ma) >> 8) as u8;
             }
         }
     }
 
-    fn (z *Rasterizer) rasterizeDstAlphaSrcOpaqueOpSrc(dst *image.Alpha, r image.Rectangle) {
-        // TODO: non-zero vs even-odd winding?
-        if r == dst.Bounds() && r == z.Bounds() {
-            // We bypass the z.accumulateMask step and convert straight from
-            // z.bufF32 or z.bufU32 to dst.Pix.
-            if z.useFloatingPointMath {
-                if haveFloatingAccumulateSIMD {
-                    floatingAccumulateOpSrcSIMD(dst.Pix, z.bufF32)
-                } else {
-                    floatingAccumulateOpSrc(dst.Pix, z.bufF32)
-                }
-            } else {
-                if haveFixedAccumulateSIMD {
-                    fixedAccumulateOpSrcSIMD(dst.Pix, z.bufU32)
+    /// Writes the coverage of the accumulated path into `dst`.
+    ///
+    /// Has the same fast path as `alpha_opaque_over`.
+    pub fn alpha_opaque_src(&mut self, dst: &mut Alpha, r: Rectangle) {
+        if r == dst.bounds() && r == self.bounds() && dst.stride == r.dx() && !self.accumulated {
+            // We bypass the accumulate_mask step and convert straight from
+            // the f32 or u32 cells to dst.pix.
+            let n = self.size[0] * self.size[1];
+            dst.pix[..n].iter_mut().for_each(|v| *v = 0);
+            for (i, span) in self.dirty_spans() {
+                let pix = &mut dst.pix[i..i + span.len()];
+                if self.use_fpm {
+                    simd::floating_accumulate_op_src(pix, &self.buf.as_slice_f32()[span], self.fill_rule)
                 } else {
-                    fixedAccumulateOpSrc(dst.Pix, z.bufU32)
+                    simd::fixed_accumulate_op_src(pix, &self.buf.as_slice_u32()[span], self.fill_rule)
                 }
             }
             return
         }
 
-        z.accumulateMask()
-        pix := dst.Pix[dst.PixOffset(r.Min.X, r.Min.Y):]
-        for y, y1 := 0, r.Max.Y-r.Min.Y; y < y1; y++ {
-            for x, x1 := 0, r.Max.X-r.Min.X; x < x1; x++ {
-                ma := z.bufU32[y*z.size.X+x]
-
-                // This formula is like rasterizeOpSrc's, simplified for the
+        self.accumulate_mask();
+        let c = self.clip(r, dst.rect);
+        for y in c.min.y..c.max.y {
+            let my = y - r.min.y;
+            let i = dst.pix_offset(0, y);
+            for x in c.min.x..c.max.x {
+                let ma = self.mask_at(x - r.min.x, my);
+
+                // This formula is like rasterize_op_src's, simplified for the
                 // concrete dst type and opaque src assumption.
-                pix[y*dst.Stride+x] = uint8(ma >> 8)
+                dst.pix[(i + x) as usize] = (ma >> 8) as u8;
             }
         }
     }
-    */
 
+    /// Composites the accumulated path, filled with `color`, over `dst`.
+    ///
+    /// The mask's origin is placed at `r.min`: the rasterizer may be smaller
+    /// than `dst` and `r` may reach outside of it. Only the part of `r` inside
+    /// both `dst` and the mask is drawn. This holds for every method that
+    /// takes a destination rectangle.
     pub fn rgba_uniform_over(&mut self, dst: &mut RGBA, r: Rectangle, color: [u32; 4]) {
         self.accumulate_mask();
 
         let [sr, sg, sb, sa] = color;
-        let idx = dst.pix_offset(r.min.x, r.min.y);
-        let pix = &mut dst.pix[idx as usize..];
-
-        let x1 = r.max.x-r.min.x;
-        let y1 = r.max.y-r.min.y;
-        for y in 0..y1 {
-            for x in 0..x1 {
-                let idx = y * self.size[0] as isize + x;
-                let ma = self.buf.as_u32()[idx as usize];
+        let c = self.clip(r, dst.rect).intersect(self.dirty + r.min);
+        for y in c.min.y..c.max.y {
+            let my = y - r.min.y;
+            let i = dst.pix_offset(0, y);
+            let pix = &mut dst.pix;
+            for x in c.min.x..c.max.x {
+                let ma = self.mask_at(x - r.min.x, my);
 
                 // This formula is like rasterizeOpOver's, simplified for the
                 // concrete dst type and uniform src assumption.
                 let a = 0xffff - (sa * ma / 0xffff);
-                let i = (y * dst.stride + 4 * x) as usize;
+                let i = (i + 4 * x) as usize;
                 pix[i+0] = ((((pix[i+0] as u32) * 0x101 * a + sr * ma) / 0xffff) >> 8) as u8;
                 pix[i+1] = ((((pix[i+1] as u32) * 0x101 * a + sg * ma) / 0xffff) >> 8) as u8;
                 pix[i+2] = ((((pix[i+2] as u32) * 0x101 * a + sb * ma) / 0xffff) >> 8) as u8;
@@ -323,22 +371,22 @@ impl Rasterizer {
         }
     }
 
+    /// Writes the accumulated path, filled with `color`, into `dst`.
     pub fn rgba_uniform_src(&mut self, dst: &mut RGBA, r: Rectangle, color: [u32; 4]) {
         self.accumulate_mask();
-        let [sr, sg, sb, sa] = color;
-        let idx = dst.pix_offset(r.min.x, r.min.y);
-        let pix = &mut dst.pix[idx as usize..];
 
-        let x1 = r.max.x-r.min.x;
-        let y1 = r.max.y-r.min.y;
-        for y in 0..y1 {
-            for x in 0..x1 {
-                let idx = y * self.size[0] as isize + x;
-                let ma = self.buf.as_u32()[idx as usize];
+        let [sr, sg, sb, sa] = color;
+        let c = self.clip(r, dst.rect);
+        for y in c.min.y..c.max.y {
+            let my = y - r.min.y;
+            let i = dst.pix_offset(0, y);
+            let pix = &mut dst.pix;
+            for x in c.min.x..c.max.x {
+                let ma = self.mask_at(x - r.min.x, my);
 
                 // This formula is like rasterizeOpSrc's, simplified for the
                 // concrete dst type and uniform src assumption.
-                let i = (y * dst.stride + 4 * x) as usize;
+                let i = (i + 4 * x) as usize;
                 pix[i+0] = ((sr * ma / 0xffff) >> 8) as u8;
                 pix[i+1] = ((sg * ma / 0xffff) >> 8) as u8;
                 pix[i+2] = ((sb * ma / 0xffff) >> 8) as u8;
@@ -347,50 +395,182 @@ impl Rasterizer {
         }
     }
 
-    /*
-    fn (z *Rasterizer) rasterizeOpOver(dst draw.Image, r image.Rectangle, src image.Image, sp image.Point) {
-        z.accumulateMask()
-        out := color.RGBA64{}
-        outc := color.Color(&out)
-        for y, y1 := 0, r.Max.Y-r.Min.Y; y < y1; y++ {
-            for x, x1 := 0, r.Max.X-r.Min.X; x < x1; x++ {
-                sr, sg, sb, sa := src.At(sp.X+x, sp.Y+y).RGBA()
-                ma := z.bufU32[y*z.size.X+x]
+    /// Fills the accumulated path with the pixels of `src`,
+    /// mapping `r.min` to `sp`, composited over `dst`.
+    ///
+    /// Like everything in this package, `src` is treated as alpha-premultiplied.
+    /// Pixels outside of `src.rect` are transparent.
+    pub fn rasterize_op_over(&mut self, dst: &mut RGBA, r: Rectangle, src: &RGBA, sp: Point) {
+        self.shade_over(dst, r, |x, y| widen(src.at(sp.x+x, sp.y+y)))
+    }
+
+    /// Fills the accumulated path with the pixels of `src`,
+    /// mapping `r.min` to `sp`, replacing `dst`.
+    pub fn rasterize_op_src(&mut self, dst: &mut RGBA, r: Rectangle, src: &RGBA, sp: Point) {
+        self.shade_src(dst, r, |x, y| widen(src.at(sp.x+x, sp.y+y)))
+    }
+
+    /// Composites the mask over `dst`, taking the color of each mask pixel from `shade`.
+    pub(crate) fn shade_over<F>(&mut self, dst: &mut RGBA, r: Rectangle, shade: F)
+        where F: Fn(isize, isize) -> [u32; 4]
+    {
+        self.accumulate_mask();
+        let c = self.clip(r, dst.rect).intersect(self.dirty + r.min);
+        for y in c.min.y..c.max.y {
+            let (my, i) = (y - r.min.y, dst.pix_offset(0, y));
+            let pix = &mut dst.pix;
+            for x in c.min.x..c.max.x {
+                let mx = x - r.min.x;
+                let ma = self.mask_at(mx, my);
+                if ma == 0 {
+                    continue
+                }
+                let [sr, sg, sb, sa] = shade(mx, my);
 
                 // This algorithm comes from the standard library's image/draw
                 // package.
-                dr, dg, db, da := dst.At(r.Min.X+x, r.Min.Y+y).RGBA()
-                a := 0xffff - (sa * ma / 0xffff)
-                out.R = uint16((dr*a + sr*ma) / 0xffff)
-                out.G = uint16((dg*a + sg*ma) / 0xffff)
-                out.B = uint16((db*a + sb*ma) / 0xffff)
-                out.A = uint16((da*a + sa*ma) / 0xffff)
-
-                dst.Set(r.Min.X+x, r.Min.Y+y, outc)
+                let a = 0xffff - (sa * ma / 0xffff);
+                let i = (i + 4 * x) as usize;
+                pix[i] = ((((pix[i] as u32) * 0x101 * a + sr * ma) / 0xffff) >> 8) as u8;
+                pix[i+1] = ((((pix[i+1] as u32) * 0x101 * a + sg * ma) / 0xffff) >> 8) as u8;
+                pix[i+2] = ((((pix[i+2] as u32) * 0x101 * a + sb * ma) / 0xffff) >> 8) as u8;
+                pix[i+3] = ((((pix[i+3] as u32) * 0x101 * a + sa * ma) / 0xffff) >> 8) as u8;
             }
         }
     }
 
-    fn (z *Rasterizer) rasterizeOpSrc(dst draw.Image, r image.Rectangle, src image.Image, sp image.Point) {
-        z.accumulateMask()
-        out := color.RGBA64{}
-        outc := color.Color(&out)
-        for y, y1 := 0, r.Max.Y-r.Min.Y; y < y1; y++ {
-            for x, x1 := 0, r.Max.X-r.Min.X; x < x1; x++ {
-                sr, sg, sb, sa := src.At(sp.X+x, sp.Y+y).RGBA()
-                ma := z.bufU32[y*z.size.X+x]
+    /// Writes the mask into `dst`, taking the color of each mask pixel from `shade`.
+    pub(crate) fn shade_src<F>(&mut self, dst: &mut RGBA, r: Rectangle, shade: F)
+        where F: Fn(isize, isize) -> [u32; 4]
+    {
+        self.accumulate_mask();
+        let c = self.clip(r, dst.rect);
+        for y in c.min.y..c.max.y {
+            let (my, i) = (y - r.min.y, dst.pix_offset(0, y));
+            let pix = &mut dst.pix;
+            for x in c.min.x..c.max.x {
+                let mx = x - r.min.x;
+                let ma = self.mask_at(mx, my);
+                let [sr, sg, sb, sa] = shade(mx, my);
 
                 // This algorithm comes from the standard library's image/draw
                 // package.
-                out.R = uint16(sr * ma / 0xffff)
-                out.G = uint16(sg * ma / 0xffff)
-                out.B = uint16(sb * ma / 0xffff)
-                out.A = uint16(sa * ma / 0xffff)
+                let i = (i + 4 * x) as usize;
+                pix[i] = ((sr * ma / 0xffff) >> 8) as u8;
+                pix[i+1] = ((sg * ma / 0xffff) >> 8) as u8;
+                pix[i+2] = ((sb * ma / 0xffff) >> 8) as u8;
+                pix[i+3] = ((sa * ma / 0xffff) >> 8) as u8;
+            }
+        }
+    }
+
+    // clip returns the part of r inside both the dst bounds and the mask,
+    // whose origin is at r.min.
+    fn clip(&self, r: Rectangle, dst: Rectangle) -> Rectangle {
+        r.intersect(dst).intersect(self.bounds() + r.min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(z: &mut Rasterizer) {
+        z.move_to(2.0, 1.5);
+        z.line_to(13.25, 4.0);
+        z.line_to(11.0, 14.5);
+        z.line_to(1.5, 10.0);
+        z.close_path();
+    }
+
+    #[test]
+    fn stamp_twice() {
+        let color = [0xffff, 0x8000, 0, 0xffff];
+        let mut buf = vec![0; 4 * 16 * 16];
+        let mut want = RGBA::from_buf(&mut buf, Rectangle::from_size(16, 16));
+        let mut z = Rasterizer::new(16, 16);
+        quad(&mut z);
+        z.rgba_uniform_over(&mut want, z.bounds(), color);
+
+        // The same path, drawn at two places of a wider image.
+        let mut buf = vec![0; 4 * 40 * 16];
+        let mut got = RGBA::from_buf(&mut buf, Rectangle::from_size(40, 16));
+        let mut z = Rasterizer::new(16, 16);
+        quad(&mut z);
+        for &x in &[0, 20] {
+            z.rgba_uniform_over(&mut got, z.bounds() + Point { x, y: 0 }, color);
+        }
+        for &x in &[0, 20] {
+            for y in 0..16 {
+                for i in 0..16 {
+                    assert_eq!(got.at(x + i, y), want.at(i, y), "{} {}", x + i, y);
+                }
+            }
+        }
+    }
 
-                dst.Set(r.Min.X+x, r.Min.Y+y, outc)
+    #[test]
+    fn alpha_after_accumulate() {
+        let r = Rectangle::from_size(16, 16);
+        let mut want = vec![0; 16 * 16];
+        let mut z = Rasterizer::new(16, 16);
+        quad(&mut z);
+        z.alpha_opaque_src(&mut Alpha::from_buf(&mut want, r), r);
+        assert!(want.contains(&0xff));
+
+        // The fast path reads the area values, the offset draw accumulates
+        // them, and the last draw has to notice it.
+        let mut z = Rasterizer::new(16, 16);
+        quad(&mut z);
+        let (mut a, mut b, mut c) = (vec![0; 16 * 16], vec![0; 32 * 16], vec![0; 16 * 16]);
+        z.alpha_opaque_src(&mut Alpha::from_buf(&mut a, r), r);
+        let mut wide = Alpha::from_buf(&mut b, Rectangle::from_size(32, 16));
+        z.alpha_opaque_src(&mut wide, r + Point { x: 16, y: 0 });
+        z.alpha_opaque_src(&mut Alpha::from_buf(&mut c, r), r);
+        assert_eq!(a, want);
+        assert_eq!(c, want);
+        for y in 0..16 {
+            for x in 0..16 {
+                assert_eq!(wide.at(16 + x, y), want[(y * 16 + x) as usize]);
             }
         }
     }
-    */
 
+    #[test]
+    fn storage_follows_path() {
+        let r = Rectangle::from_size(16, 16);
+        let mut want = vec![0; 16 * 16];
+        let mut z = Rasterizer::new(16, 16);
+        quad(&mut z);
+        z.alpha_opaque_src(&mut Alpha::from_buf(&mut want, r), r);
+
+        let mut z = Rasterizer::new(500, 500);
+        quad(&mut z);
+        let m = z.mask_rect();
+        assert!(m.dx() <= 32 && m.dy() <= 32, "{:?}", m);
+        let mut got = vec![0; 16 * 16];
+        z.alpha_opaque_src(&mut Alpha::from_buf(&mut got, r), r);
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn right_of_bounds() {
+        // Only the left edge of the rectangle is inside, and its coverage
+        // runs to the right edge of the bounds.
+        let r = Rectangle::from_size(16, 8);
+        let mut z = Rasterizer::new(16, 8);
+        z.move_to(10.0, 2.0);
+        z.line_to(40.0, 2.0);
+        z.line_to(40.0, 6.0);
+        z.line_to(10.0, 6.0);
+        z.close_path();
+        let mut got = vec![0; 16 * 8];
+        z.alpha_opaque_src(&mut Alpha::from_buf(&mut got, r), r);
+        for y in 0..8 {
+            for x in 0..16 {
+                let want = if x >= 10 && (2..6).contains(&y) { 0xff } else { 0 };
+                assert_eq!(got[y * 16 + x], want, "{} {}", x, y);
+            }
+        }
+    }
 }
@@ -0,0 +1,276 @@
+//! Minimal XML reader.
+//!
+//! Only what the SVG renderer needs: elements and their attributes.
+//! Text, comments, processing instructions and the doctype are skipped,
+//! and namespaces are not resolved, so names keep their prefix.
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Element {
+    pub name: String,
+    /// Attributes in document order, with entities replaced.
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    /// Returns the value of the attribute `name`, if present.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Error returned by `parse`, with the byte offset where parsing stopped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    pub pos: usize,
+    pub msg: &'static str,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.msg, self.pos)
+    }
+}
+
+impl std::error::Error for Error {}
+
+// MAX_DEPTH is the deepest nesting of elements that parse accepts, so that
+// malformed input can't overflow the stack.
+const MAX_DEPTH: usize = 512;
+
+/// Parses an XML document and returns its root element.
+pub fn parse(src: &str) -> Result<Element, Error> {
+    let mut p = Parser { src: src.as_bytes(), pos: 0, depth: 0 };
+    p.misc()?;
+    if p.peek() != Some(b'<') {
+        return Err(p.error("expected the root element"));
+    }
+    let root = p.element()?;
+    p.misc()?;
+    if p.pos != p.src.len() {
+        return Err(p.error("trailing characters"));
+    }
+    Ok(root)
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &'static str) -> Error {
+        Error { pos: self.pos, msg }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.pos).cloned()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        self.src[self.pos..].starts_with(s.as_bytes())
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), Error> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    // skip_past moves after the next occurrence of end.
+    fn skip_past(&mut self, end: &str, msg: &'static str) -> Result<(), Error> {
+        match self.src[self.pos..].windows(end.len()).position(|w| w == end.as_bytes()) {
+            Some(i) => {
+                self.pos += i + end.len();
+                Ok(())
+            }
+            None => Err(self.error(msg)),
+        }
+    }
+
+    // misc skips whitespace, comments, processing instructions and the
+    // doctype, which may appear around the root element.
+    fn misc(&mut self) -> Result<(), Error> {
+        loop {
+            self.skip_ws();
+            if self.starts_with("<!--") {
+                self.skip_past("-->", "unterminated comment")?;
+            } else if self.starts_with("<?") {
+                self.skip_past("?>", "unterminated processing instruction")?;
+            } else if self.starts_with("<!DOCTYPE") {
+                self.doctype()?;
+            } else {
+                return Ok(())
+            }
+        }
+    }
+
+    // doctype skips a doctype declaration, which may hold an internal
+    // subset in brackets.
+    fn doctype(&mut self) -> Result<(), Error> {
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                b'[' => depth += 1,
+                b']' => depth -= 1,
+                b'>' if depth == 0 => return Ok(()),
+                _ => (),
+            }
+        }
+        Err(self.error("unterminated doctype"))
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_whitespace() || c == b'=' || c == b'>' || c == b'/' || c == b'<' {
+                break
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error("expected a name"));
+        }
+        // We only stop at ASCII bytes, so the name is always valid UTF-8.
+        Ok(std::str::from_utf8(&self.src[start..self.pos]).unwrap().to_string())
+    }
+
+    fn element(&mut self) -> Result<Element, Error> {
+        self.expect(b'<')?;
+        let mut el = Element { name: self.name()?, ..Element::default() };
+
+        loop {
+            self.skip_ws();
+            match self.peek() {
+                Some(b'/') => {
+                    self.pos += 1;
+                    self.expect(b'>')?;
+                    return Ok(el)
+                }
+                Some(b'>') => {
+                    self.pos += 1;
+                    break
+                }
+                None => return Err(self.error("unexpected end of input")),
+                _ => {
+                    let name = self.name()?;
+                    self.skip_ws();
+                    self.expect(b'=')?;
+                    self.skip_ws();
+                    let value = self.attr_value()?;
+                    el.attrs.push((name, value));
+                }
+            }
+        }
+
+        loop {
+            // Text is skipped, but it still can't hold a bare '<'.
+            while let Some(c) = self.peek() {
+                if c == b'<' { break }
+                self.pos += 1;
+            }
+            if self.peek().is_none() {
+                return Err(self.error("unexpected end of input"));
+            }
+            if self.starts_with("</") {
+                self.pos += 2;
+                let start = self.pos;
+                if self.name()? != el.name {
+                    self.pos = start;
+                    return Err(self.error("mismatched closing tag"));
+                }
+                self.skip_ws();
+                self.expect(b'>')?;
+                return Ok(el)
+            } else if self.starts_with("<!--") {
+                self.skip_past("-->", "unterminated comment")?;
+            } else if self.starts_with("<![CDATA[") {
+                self.skip_past("]]>", "unterminated CDATA section")?;
+            } else if self.starts_with("<?") {
+                self.skip_past("?>", "unterminated processing instruction")?;
+            } else if self.depth == MAX_DEPTH {
+                return Err(self.error("nesting too deep"));
+            } else {
+                self.depth += 1;
+                el.children.push(self.element()?);
+                self.depth -= 1;
+            }
+        }
+    }
+
+    fn attr_value(&mut self) -> Result<String, Error> {
+        let quote = match self.peek() {
+            Some(q @ b'"') | Some(q @ b'\'') => q,
+            _ => return Err(self.error("expected a quoted value")),
+        };
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            while let Some(c) = self.peek() {
+                if c == quote || c == b'&' || c == b'<' { break }
+                self.pos += 1;
+            }
+            out.push_str(std::str::from_utf8(&self.src[start..self.pos]).unwrap());
+            match self.peek() {
+                None => return Err(self.error("unterminated value")),
+                Some(b'<') => return Err(self.error("unexpected '<' in value")),
+                Some(b'&') => out.push(self.entity()?),
+                _ => {
+                    self.pos += 1;
+                    return Ok(out)
+                }
+            }
+        }
+    }
+
+    fn entity(&mut self) -> Result<char, Error> {
+        let start = self.pos;
+        let end = self.src[start..].iter().take(12).position(|&c| c == b';')
+            .ok_or_else(|| self.error("unterminated entity"))?;
+        let name = std::str::from_utf8(&self.src[start + 1..start + end]).unwrap();
+        let c = match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if name.starts_with("#x") => u32::from_str_radix(&name[2..], 16).ok().and_then(std::char::from_u32),
+            _ if name.starts_with('#') => name[1..].parse().ok().and_then(std::char::from_u32),
+            _ => None,
+        };
+        let c = c.ok_or_else(|| self.error("unknown entity"))?;
+        self.pos += end + 1;
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nesting() {
+        let ok = "<a>".repeat(MAX_DEPTH + 1) + &"</a>".repeat(MAX_DEPTH + 1);
+        assert!(parse(&ok).is_ok());
+        let deep = "<a>".repeat(MAX_DEPTH + 2) + &"</a>".repeat(MAX_DEPTH + 2);
+        assert_eq!(parse(&deep), Err(Error { pos: 3 * (MAX_DEPTH + 1), msg: "nesting too deep" }));
+        let deep = "<a>".repeat(100_000);
+        assert_eq!(parse(&deep).unwrap_err().msg, "nesting too deep");
+    }
+}